@@ -0,0 +1,153 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::filesystem::app_data_dir;
+
+/// The cache drops its least recently used archives past this size.
+pub const MAX_CACHE_BYTES: u64 = 1024 * 1024 * 1024;
+/// When each archive was last read, by file name. Archives that were never read count
+/// as used when they were written.
+const LAST_USED_FILE: &str = "last_used.json";
+
+/// What the archive cache holds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheStats {
+    pub archives: usize,
+    pub bytes: u64,
+}
+
+fn cache_dir() -> io::Result<PathBuf> {
+    app_data_dir()
+        .map(|dir| dir.join("archives"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))
+}
+
+fn archive_name(id: &str, time_updated: DateTime<Utc>) -> String {
+    format!("{}-{}.zip", id, time_updated.timestamp())
+}
+
+/// Whether the archive of this version of the mod is cached, without reading it.
+pub fn is_archive_cached(id: &str, time_updated: DateTime<Utc>) -> bool {
+    cache_dir().map_or(false, |dir| {
+        dir.join(archive_name(id, time_updated)).is_file()
+    })
+}
+
+/// The downloaded archive of this version of the mod, if it's cached.
+pub fn cached_archive(id: &str, time_updated: DateTime<Utc>) -> Option<Vec<u8>> {
+    let dir = cache_dir().ok()?;
+    let name = archive_name(id, time_updated);
+    let zip_bytes = fs::read(dir.join(&name)).ok()?;
+
+    // Only changes what's evicted first, so failing to write it is fine
+    let mut last_used = read_last_used(&dir);
+    last_used.insert(name, Utc::now().timestamp());
+    write_last_used(&dir, &last_used).ok();
+
+    Some(zip_bytes)
+}
+
+/// Keeps the archive of this version of the mod, replacing the other versions of it, and
+/// drops the least recently used archives if the cache grew too big.
+pub fn cache_archive(id: &str, time_updated: DateTime<Utc>, zip_bytes: &[u8]) -> io::Result<()> {
+    let dir = cache_dir()?;
+    fs::create_dir_all(&dir)?;
+
+    let name = archive_name(id, time_updated);
+    let other_versions = format!("{}-", id);
+    for (path, _, _) in list_archives()? {
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        if file_name.starts_with(&other_versions) && file_name != name {
+            fs::remove_file(path)?;
+        }
+    }
+
+    fs::write(dir.join(name), zip_bytes)?;
+
+    let mut last_used = read_last_used(&dir);
+    let mut archives = list_archives()?;
+    archives.sort_by_key(|(path, _, modified)| {
+        let used = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| last_used.get(name))
+            .map(|time| UNIX_EPOCH + Duration::from_secs(*time as u64));
+        used.map_or(*modified, |used| used.max(*modified))
+    });
+    let mut total = archives.iter().map(|(_, size, _)| size).sum::<u64>();
+    for (path, size, _) in archives {
+        if total <= MAX_CACHE_BYTES {
+            break;
+        }
+        fs::remove_file(&path)?;
+        total -= size;
+    }
+
+    // Forget the archives that are gone
+    let names = list_archives()?
+        .into_iter()
+        .filter_map(|(path, _, _)| path.file_name()?.to_str().map(|name| name.to_string()))
+        .collect::<Vec<_>>();
+    last_used.retain(|name, _| names.contains(name));
+    write_last_used(&dir, &last_used)
+}
+
+fn read_last_used(dir: &Path) -> HashMap<String, i64> {
+    fs::read_to_string(dir.join(LAST_USED_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn write_last_used(dir: &Path, last_used: &HashMap<String, i64>) -> io::Result<()> {
+    fs::write(dir.join(LAST_USED_FILE), serde_json::to_string(last_used)?)
+}
+
+pub fn cache_stats() -> CacheStats {
+    let archives = list_archives().unwrap_or_default();
+    CacheStats {
+        archives: archives.len(),
+        bytes: archives.iter().map(|(_, size, _)| size).sum(),
+    }
+}
+
+/// Deletes every cached archive.
+pub fn purge_cache() -> io::Result<()> {
+    for (path, _, _) in list_archives()? {
+        fs::remove_file(path)?;
+    }
+
+    match fs::remove_file(cache_dir()?.join(LAST_USED_FILE)) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Path, size and modification time of the cached archives.
+fn list_archives() -> io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+    let entries = match fs::read_dir(cache_dir()?) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+
+    let mut archives = vec![];
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let is_archive = entry.path().extension().map_or(false, |ext| ext == "zip");
+        if metadata.is_file() && is_archive {
+            archives.push((entry.path(), metadata.len(), metadata.modified()?));
+        }
+    }
+    Ok(archives)
+}
@@ -0,0 +1,194 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use zip::{write::FileOptions, ZipArchive, ZipWriter};
+
+use crate::{data::InstalledMod, filesystem, store, versions::store_version};
+
+static MANIFEST_NAME: &str = "bundle.json";
+
+/// Everything about the bundled mods but their files.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct BundleManifest {
+    mods: Vec<InstalledMod>,
+}
+
+/// A mod carried by a bundle, with its preview image when the bundle has one.
+#[derive(Debug, Clone)]
+pub struct BundledMod {
+    pub installed: InstalledMod,
+    pub preview: Option<Vec<u8>>,
+}
+
+/// Writes the mods, their catalog metadata and preview images to a single zip file that
+/// can be imported without internet. Previews come from the mod folder, or are downloaded
+/// when the folder has none.
+pub async fn export_bundle(
+    client: Client,
+    path: PathBuf,
+    barony_path: String,
+    mods: Vec<InstalledMod>,
+) -> Result<usize, String> {
+    let mut previews = vec![];
+    for installed in &mods {
+        previews.push(find_preview(client.clone(), &barony_path, installed).await);
+    }
+
+    write_bundle(&path, &barony_path, &mods, &previews).map_err(|err| err.to_string())?;
+    Ok(mods.len())
+}
+
+async fn find_preview(
+    client: Client,
+    barony_path: &str,
+    installed: &InstalledMod,
+) -> Option<Vec<u8>> {
+    if let Some(preview_path) = filesystem::find_mod_preview(barony_path, &installed.folder) {
+        return fs::read(preview_path).ok();
+    }
+
+    if installed.workshop.preview_url.is_empty() {
+        return None;
+    }
+
+    let response = client
+        .get(&installed.workshop.preview_url)
+        .send()
+        .await
+        .ok()?;
+    response.bytes().await.ok().map(|bytes| bytes.to_vec())
+}
+
+fn write_bundle(
+    path: &Path,
+    barony_path: &str,
+    mods: &[InstalledMod],
+    previews: &[Option<Vec<u8>>],
+) -> io::Result<()> {
+    let mut bundle = ZipWriter::new(fs::File::create(path)?);
+
+    let manifest = BundleManifest {
+        mods: mods.to_vec(),
+    };
+    bundle.start_file(MANIFEST_NAME, FileOptions::default())?;
+    bundle.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+
+    for (installed, preview) in mods.iter().zip(previews) {
+        let id = &installed.workshop.id;
+        let mod_path = Path::new(barony_path).join("mods/").join(&installed.folder);
+
+        for relative_path in filesystem::list_mod_files(barony_path, &installed.folder)? {
+            bundle.start_file(
+                format!("mods/{}/{}", id, relative_path),
+                FileOptions::default(),
+            )?;
+            io::copy(
+                &mut fs::File::open(mod_path.join(&relative_path))?,
+                &mut bundle,
+            )?;
+        }
+
+        if let Some(preview) = preview {
+            bundle.start_file(format!("previews/{}", id), FileOptions::default())?;
+            bundle.write_all(preview)?;
+        }
+    }
+
+    bundle.finish()?;
+    Ok(())
+}
+
+/// Extracts every mod of the bundle into `mods/`, replacing the folders that already
+/// exist, and returns the imported mods. Replaced mods go to the versions store of the
+/// installation when `current` tracks them, and are backed up otherwise.
+pub fn import_bundle(
+    path: &Path,
+    installation: &str,
+    barony_path: &str,
+    current: &HashMap<String, InstalledMod>,
+) -> io::Result<Vec<BundledMod>> {
+    let mut bundle = ZipArchive::new(fs::File::open(path)?)?;
+
+    let manifest: BundleManifest = {
+        let file = bundle
+            .by_name(MANIFEST_NAME)
+            .map_err(|_| invalid_bundle("the file is not a mod bundle"))?;
+        serde_json::from_reader(file)?
+    };
+
+    let mut imported = vec![];
+    for installed in manifest.mods {
+        let id = installed.workshop.id.clone();
+        let mod_path = Path::new(barony_path).join("mods/").join(&installed.folder);
+        if installed.folder.is_empty() || !is_plain_relative(Path::new(&installed.folder)) {
+            return Err(invalid_bundle(
+                "a mod folder is outside of the mods directory",
+            ));
+        }
+
+        match current.get(&id) {
+            Some(current) if current.folder == installed.folder => {
+                store_version(installation, barony_path, &id, current)?
+            }
+            _ if filesystem::is_mod_folder_present(barony_path, &installed.folder) => {
+                filesystem::backup_mod(barony_path, &installed.folder)?;
+            }
+            _ => {}
+        }
+
+        // Like downloaded mods, the files go to the store and the mod folder links to them
+        let prefix = format!("mods/{}/", id);
+        store::install_files(&mod_path, |staging| {
+            fs::create_dir_all(staging)?;
+            for i in 0..bundle.len() {
+                let mut file = bundle.by_index(i)?;
+                let relative_path = match file.name().strip_prefix(&prefix) {
+                    Some(relative_path) if !file.name().ends_with('/') => {
+                        PathBuf::from(relative_path)
+                    }
+                    _ => continue,
+                };
+                if !is_plain_relative(&relative_path) {
+                    return Err(invalid_bundle(
+                        "the bundle has files outside of the mod folder",
+                    ));
+                }
+
+                let target = staging.join(relative_path);
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                io::copy(&mut file, &mut fs::File::create(target)?)?;
+            }
+            Ok(())
+        })?;
+
+        let preview = match bundle.by_name(&format!("previews/{}", id)) {
+            Ok(mut file) => {
+                let mut bytes = vec![];
+                file.read_to_end(&mut bytes)?;
+                Some(bytes)
+            }
+            Err(_) => None,
+        };
+
+        imported.push(BundledMod { installed, preview });
+    }
+
+    Ok(imported)
+}
+
+fn is_plain_relative(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
+
+fn invalid_bundle(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+use serde_json::{json, Map, Value};
+
+use crate::data::SteamWorkshopMod;
+
+/// The parsed workshop catalog, along with a report of the entries that had to be
+/// repaired or were thrown away while parsing it.
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    pub mods: Vec<SteamWorkshopMod>,
+    pub diagnostics: CatalogDiagnostics,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CatalogDiagnostics {
+    pub rejected: Vec<CatalogIssue>,
+    pub repaired: Vec<CatalogIssue>,
+}
+
+impl CatalogDiagnostics {
+    pub fn is_empty(&self) -> bool {
+        self.rejected.is_empty() && self.repaired.is_empty()
+    }
+}
+
+/// Something that was wrong with a single catalog entry.
+#[derive(Debug, Clone)]
+pub struct CatalogIssue {
+    /// Position of the entry inside the catalog list
+    pub index: usize,
+    pub id: Option<String>,
+    pub reason: String,
+}
+
+impl Display for CatalogIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.id {
+            Some(id) => write!(f, "Entry #{} ({}): {}", self.index, id, self.reason),
+            None => write!(f, "Entry #{}: {}", self.index, self.reason),
+        }
+    }
+}
+
+/// Parses the catalog one entry at a time, so a single malformed entry can't make the
+/// whole catalog unusable. Entries missing non-essential fields are repaired with
+/// defaults, while entries that can't be identified or dated are rejected.
+pub fn parse_catalog(value: Value) -> Catalog {
+    let mut catalog = Catalog::default();
+
+    let entries = match value {
+        Value::Array(entries) => entries,
+        _ => {
+            catalog.diagnostics.rejected.push(CatalogIssue {
+                index: 0,
+                id: None,
+                reason: "the catalog is not a list of mods".to_string(),
+            });
+            return catalog;
+        }
+    };
+
+    for (index, entry) in entries.into_iter().enumerate() {
+        let mut object = match entry {
+            Value::Object(object) => object,
+            _ => {
+                catalog.diagnostics.rejected.push(CatalogIssue {
+                    index,
+                    id: None,
+                    reason: "entry is not an object".to_string(),
+                });
+                continue;
+            }
+        };
+
+        let id = entry_id(&object);
+        let issue = |reason: String| CatalogIssue {
+            index,
+            id: id.clone(),
+            reason,
+        };
+
+        if id.is_none() {
+            catalog
+                .diagnostics
+                .rejected
+                .push(issue("missing workshop id".to_string()));
+            continue;
+        }
+        // Steam ids may come as numbers, but the rest of the app expects strings
+        object.insert("id".to_string(), Value::String(id.clone().unwrap()));
+
+        let mut repairs = repair_missing_fields(&mut object);
+        match repair_timestamps(&mut object) {
+            Ok(mut timestamp_repairs) => repairs.append(&mut timestamp_repairs),
+            Err(reason) => {
+                catalog.diagnostics.rejected.push(issue(reason));
+                continue;
+            }
+        }
+
+        match serde_json::from_value::<SteamWorkshopMod>(Value::Object(object)) {
+            Ok(mod_) => {
+                if !repairs.is_empty() {
+                    catalog.diagnostics.repaired.push(issue(repairs.join(", ")));
+                }
+                catalog.mods.push(mod_);
+            }
+            Err(err) => catalog.diagnostics.rejected.push(issue(err.to_string())),
+        }
+    }
+
+    catalog
+}
+
+fn entry_id(object: &Map<String, Value>) -> Option<String> {
+    match object.get("id") {
+        Some(Value::String(id)) if !id.is_empty() => Some(id.clone()),
+        Some(Value::Number(id)) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// Fills the fields the app can live without with sane defaults, returning a description
+/// of every repair made. A field missing from an entry written with a schema older than
+/// the one that introduced it is expected, and is filled silently.
+fn repair_missing_fields(object: &mut Map<String, Value>) -> Vec<String> {
+    // Field, default value and the schema version that introduced it
+    let defaults = [
+        ("preview_url", json!(""), 1),
+        ("description", json!(""), 1),
+        ("file_size", json!(0), 1),
+        ("views", json!(0), 1),
+        ("tags", json!([]), 1),
+        ("votes", json!({ "up": 0, "down": 0 }), 1),
+        ("creator", json!(""), 2),
+        ("favorited", json!(0), 2),
+        ("subscriptions", json!(0), 2),
+        ("children", json!([]), 2),
+        ("previews", json!([]), 2),
+    ];
+
+    let mut repairs = vec![];
+    let schema = match object.get("schema").map(Value::as_u64) {
+        None => 1,
+        Some(Some(schema)) if schema >= 1 => schema,
+        Some(_) => {
+            repairs.push("invalid `schema`".to_string());
+            1
+        }
+    };
+    object.insert("schema".to_string(), json!(schema));
+
+    for (field, default, since) in defaults {
+        if !object.contains_key(field) && since > schema {
+            object.insert(field.to_string(), default);
+            continue;
+        }
+
+        let valid = match object.get(field) {
+            Some(Value::String(_)) => default.is_string(),
+            Some(Value::Number(n)) => default.is_number() && n.is_u64(),
+            Some(Value::Array(items)) => default.is_array() && items.iter().all(Value::is_string),
+            Some(Value::Object(votes)) => {
+                default.is_object()
+                    && ["up", "down"]
+                        .iter()
+                        .all(|key| votes.get(*key).map_or(false, Value::is_u64))
+            }
+            _ => false,
+        };
+
+        if !valid {
+            repairs.push(format!("invalid or missing `{}`", field));
+            object.insert(field.to_string(), default);
+        }
+    }
+
+    let has_title = matches!(object.get("title"), Some(Value::String(title)) if !title.is_empty());
+    if !has_title {
+        let id = object["id"].as_str().unwrap_or_default().to_string();
+        repairs.push("invalid or missing `title`".to_string());
+        object.insert(
+            "title".to_string(),
+            Value::String(format!("Workshop item {}", id)),
+        );
+    }
+
+    repairs
+}
+
+/// An entry with a single bad timestamp borrows the other one, but an entry without any
+/// usable timestamp can't be compared against installed mods and is rejected.
+fn repair_timestamps(object: &mut Map<String, Value>) -> Result<Vec<String>, String> {
+    let created = object.get("time_created").and_then(parse_timestamp);
+    let updated = object.get("time_updated").and_then(parse_timestamp);
+
+    match (created, updated) {
+        (Some(_), Some(_)) => Ok(vec![]),
+        (Some(created), None) => {
+            object.insert("time_updated".to_string(), json!(created));
+            Ok(vec!["invalid `time_updated`".to_string()])
+        }
+        (None, Some(updated)) => {
+            object.insert("time_created".to_string(), json!(updated));
+            Ok(vec!["invalid `time_created`".to_string()])
+        }
+        (None, None) => Err("invalid `time_created` and `time_updated`".to_string()),
+    }
+}
+
+fn parse_timestamp(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.trim().parse::<i64>().ok(),
+        _ => None,
+    }
+    .filter(|timestamp| *timestamp >= 0)
+}
+
+/// Differences between the catalog currently loaded and a freshly fetched one.
+#[derive(Debug, Clone, Default)]
+pub struct CatalogDiff {
+    pub added: Vec<SteamWorkshopMod>,
+    pub changed: Vec<SteamWorkshopMod>,
+    /// Ids of the mods that are not in the new catalog anymore
+    pub removed: Vec<String>,
+}
+
+pub fn diff_catalog<'a>(
+    current: impl Iterator<Item = &'a SteamWorkshopMod>,
+    new: Vec<SteamWorkshopMod>,
+) -> CatalogDiff {
+    let mut current = current
+        .map(|mod_| (mod_.id.as_str(), mod_))
+        .collect::<HashMap<_, _>>();
+    let mut diff = CatalogDiff::default();
+
+    for mod_ in new {
+        match current.remove(mod_.id.as_str()) {
+            Some(old) if *old == mod_ => (),
+            Some(_) => diff.changed.push(mod_),
+            None => diff.added.push(mod_),
+        }
+    }
+
+    diff.removed = current.keys().map(|id| id.to_string()).collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, time_updated: i64) -> Value {
+        json!({
+            "id": id,
+            "title": format!("Mod {}", id),
+            "time_created": 100,
+            "time_updated": time_updated,
+            "preview_url": "",
+            "description": "",
+            "file_size": 10,
+            "views": 3,
+            "tags": ["Misc"],
+            "votes": { "up": 1, "down": 0 },
+        })
+    }
+
+    #[test]
+    fn rejects_a_catalog_that_is_not_a_list() {
+        let catalog = parse_catalog(json!({ "mods": [] }));
+
+        assert!(catalog.mods.is_empty());
+        assert_eq!(catalog.diagnostics.rejected.len(), 1);
+    }
+
+    #[test]
+    fn rejects_malformed_entries_and_keeps_the_others() {
+        let catalog = parse_catalog(json!([
+            entry("1", 200),
+            "not an object",
+            { "title": "No id", "time_created": 1, "time_updated": 1 },
+            { "id": "4", "time_created": "never", "time_updated": -5 },
+            entry("5", 300),
+        ]));
+
+        let ids = catalog
+            .mods
+            .iter()
+            .map(|mod_| mod_.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["1", "5"]);
+
+        let rejected = catalog
+            .diagnostics
+            .rejected
+            .iter()
+            .map(|issue| (issue.index, issue.id.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(rejected, [(1, None), (2, None), (3, Some("4".to_string()))]);
+    }
+
+    #[test]
+    fn repairs_ids_titles_and_timestamps() {
+        let catalog = parse_catalog(json!([
+            { "id": 42, "title": "", "time_created": "100", "time_updated": null },
+        ]));
+
+        let mod_ = &catalog.mods[0];
+        assert_eq!(mod_.id, "42");
+        assert_eq!(mod_.title, "Workshop item 42");
+        assert_eq!(mod_.time_updated, mod_.time_created);
+        assert_eq!(mod_.time_created.timestamp(), 100);
+
+        let repaired = &catalog.diagnostics.repaired[0];
+        assert!(repaired.reason.contains("`title`"));
+        assert!(repaired.reason.contains("`time_updated`"));
+    }
+
+    #[test]
+    fn fills_fields_newer_than_the_schema_silently() {
+        let catalog = parse_catalog(json!([entry("1", 200)]));
+
+        assert!(catalog.diagnostics.is_empty());
+        let mod_ = &catalog.mods[0];
+        assert_eq!(mod_.schema, 1);
+        assert!(mod_.children.is_empty());
+        assert_eq!(mod_.creator, "");
+    }
+
+    #[test]
+    fn reports_fields_missing_from_their_schema() {
+        let mut value = entry("1", 200);
+        value["schema"] = json!(2);
+        value["votes"] = json!({ "up": -1, "down": 0 });
+        let catalog = parse_catalog(json!([value]));
+
+        let reason = &catalog.diagnostics.repaired[0].reason;
+        assert!(reason.contains("`votes`"));
+        assert!(reason.contains("`children`"));
+        assert_eq!(catalog.mods[0].votes.up, 0);
+    }
+
+    #[test]
+    fn repairs_an_invalid_schema() {
+        let mut value = entry("1", 200);
+        value["schema"] = json!("two");
+        let catalog = parse_catalog(json!([value]));
+
+        assert_eq!(catalog.mods[0].schema, 1);
+        assert!(catalog.diagnostics.repaired[0].reason.contains("`schema`"));
+    }
+
+    #[test]
+    fn diffs_added_changed_and_removed_mods() {
+        let current = parse_catalog(json!([entry("1", 200), entry("2", 200), entry("3", 200)]));
+        let new = parse_catalog(json!([entry("1", 200), entry("2", 300), entry("4", 200)]));
+
+        let diff = diff_catalog(current.mods.iter(), new.mods);
+
+        let ids =
+            |mods: &[SteamWorkshopMod]| mods.iter().map(|mod_| mod_.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&diff.added), ["4"]);
+        assert_eq!(ids(&diff.changed), ["2"]);
+        assert_eq!(diff.removed, ["3"]);
+    }
+}
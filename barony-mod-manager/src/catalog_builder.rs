@@ -0,0 +1,588 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::Path,
+    time::Duration,
+};
+
+use chrono::Utc;
+use reqwest::{header::RETRY_AFTER, Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::steam_api::{catalog_entry, SteamWebApi, BARONY_APP_ID};
+
+/// The most items `QueryFiles` and `GetPlayerSummaries` return per request.
+const PAGE_SIZE: usize = 100;
+/// `EPublishedFileQueryType::RankedByLastUpdatedDate`
+const QUERY_BY_LAST_UPDATED: &str = "21";
+const MAX_RETRIES: u32 = 5;
+/// Pause between requests, so big catalogs don't hit the rate limit right away
+const REQUEST_DELAY: Duration = Duration::from_millis(250);
+/// Items leave the workshop a few at a time, so listing less than this share of the
+/// previous catalog means the listing was cut short
+const MIN_LISTED_SHARE: f64 = 0.5;
+
+/// What changed in the catalog in one run of the builder.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChangelogEntry {
+    /// Unix time of the run
+    pub time: i64,
+    pub added: Vec<ChangedItem>,
+    pub updated: Vec<ChangedItem>,
+    pub removed: Vec<ChangedItem>,
+}
+
+impl ChangelogEntry {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangedItem {
+    pub id: String,
+    pub title: String,
+}
+
+/// Builds the catalog from the previous one, only fetching the items updated since the
+/// newest one it has, unless `full` is set. Items that left the workshop are dropped.
+/// Returns the new catalog entries sorted by id, and what changed.
+pub async fn build_catalog(
+    client: Client,
+    api: SteamWebApi,
+    previous: Vec<Value>,
+    full: bool,
+) -> Result<(Vec<Value>, ChangelogEntry), String> {
+    let key = api
+        .key
+        .clone()
+        .ok_or_else(|| "The catalog builder needs a Steam Web API key".to_string())?;
+
+    let watermark = if full {
+        None
+    } else {
+        previous
+            .iter()
+            .filter_map(|entry| entry["time_updated"].as_i64())
+            .max()
+    };
+
+    let mut fetched = query_updated_items(&client, &api, &key, watermark).await?;
+    let listed = query_all_ids(&client, &api, &key).await?;
+    if listed.is_empty() && !previous.is_empty() {
+        return Err("Steam listed no workshop items, refusing to empty the catalog".to_string());
+    }
+    if (listed.len() as f64) < previous.len() as f64 * MIN_LISTED_SHARE {
+        return Err(format!(
+            "Steam listed {} workshop items but the catalog has {}, refusing to drop them",
+            listed.len(),
+            previous.len()
+        ));
+    }
+
+    let previous = previous
+        .into_iter()
+        .filter_map(|entry| {
+            entry["id"]
+                .as_str()
+                .map(|id| (id.to_string(), entry.clone()))
+        })
+        .collect::<HashMap<_, _>>();
+
+    // Items that are listed but weren't fetched nor known, like ones made public with an
+    // old update date
+    let fetched_ids = fetched
+        .iter()
+        .filter_map(|details| details["publishedfileid"].as_str())
+        .map(|id| id.to_string())
+        .collect::<HashSet<_>>();
+    let missing = listed
+        .iter()
+        .filter(|id| !fetched_ids.contains(*id) && !previous.contains_key(*id))
+        .cloned()
+        .collect::<Vec<_>>();
+    for ids in missing.chunks(PAGE_SIZE) {
+        fetched.extend(get_details(&client, &api, &key, ids).await?);
+    }
+
+    let mut entries = fetched
+        .iter()
+        .filter(|details| details["result"].as_u64().unwrap_or(1) == 1)
+        .map(|details| {
+            let mut entry = catalog_entry(details);
+            entry["creator"] = details["creator"].clone();
+            entry
+        })
+        .collect::<Vec<_>>();
+    resolve_creator_names(&client, &api, &key, &mut entries).await?;
+
+    let mut changelog = ChangelogEntry {
+        time: Utc::now().timestamp(),
+        ..ChangelogEntry::default()
+    };
+    let mut catalog = previous.clone();
+
+    for entry in entries {
+        let id = match entry["id"].as_str() {
+            Some(id) => id.to_string(),
+            None => continue,
+        };
+
+        match previous.get(&id) {
+            None => changelog.added.push(changed_item(&entry)),
+            Some(old) if old["time_updated"].as_i64() < entry["time_updated"].as_i64() => {
+                changelog.updated.push(changed_item(&entry))
+            }
+            Some(_) => {}
+        }
+        catalog.insert(id, entry);
+    }
+
+    let listed = listed.into_iter().collect::<HashSet<_>>();
+    let removed = catalog
+        .keys()
+        .filter(|id| !listed.contains(*id))
+        .cloned()
+        .collect::<Vec<_>>();
+    for id in removed {
+        if let Some(entry) = catalog.remove(&id) {
+            changelog.removed.push(changed_item(&entry));
+        }
+    }
+
+    let mut catalog = catalog.into_iter().collect::<Vec<_>>();
+    catalog.sort_by_key(|(id, _)| (id.len(), id.clone()));
+
+    for items in [
+        &mut changelog.added,
+        &mut changelog.updated,
+        &mut changelog.removed,
+    ] {
+        items.sort_by_key(|item| (item.id.len(), item.id.clone()));
+    }
+
+    Ok((
+        catalog.into_iter().map(|(_, entry)| entry).collect(),
+        changelog,
+    ))
+}
+
+fn changed_item(entry: &Value) -> ChangedItem {
+    ChangedItem {
+        id: entry["id"].as_str().unwrap_or_default().to_string(),
+        title: entry["title"].as_str().unwrap_or_default().to_string(),
+    }
+}
+
+/// Details of the items updated at or after `watermark`, newest first. Every item when
+/// there's no watermark.
+async fn query_updated_items(
+    client: &Client,
+    api: &SteamWebApi,
+    key: &str,
+    watermark: Option<i64>,
+) -> Result<Vec<Value>, String> {
+    let mut items = vec![];
+    let mut cursor = "*".to_string();
+
+    loop {
+        let response = get_json(
+            client,
+            &api.endpoint("IPublishedFileService/QueryFiles/v1"),
+            &[
+                ("key", key),
+                ("appid", &BARONY_APP_ID.to_string()),
+                ("query_type", QUERY_BY_LAST_UPDATED),
+                ("cursor", &cursor),
+                ("numperpage", &PAGE_SIZE.to_string()),
+                ("return_details", "true"),
+                ("return_tags", "true"),
+                ("return_vote_data", "true"),
+                ("return_children", "true"),
+                ("return_previews", "true"),
+                ("strip_description_bbcode", "true"),
+            ],
+        )
+        .await?;
+
+        let page = response["response"]["publishedfiledetails"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let reached_watermark = watermark.map_or(false, |watermark| {
+            page.iter()
+                .any(|item| item["time_updated"].as_i64().unwrap_or(0) < watermark)
+        });
+
+        items.extend(page.iter().cloned().filter(|item| {
+            watermark.map_or(true, |watermark| {
+                item["time_updated"].as_i64().unwrap_or(0) >= watermark
+            })
+        }));
+
+        match next_cursor(&response, &cursor) {
+            Some(next) if !page.is_empty() && !reached_watermark => cursor = next,
+            _ => return Ok(items),
+        }
+    }
+}
+
+/// Ids of every item in the workshop, which is cheap enough to know what was removed.
+async fn query_all_ids(
+    client: &Client,
+    api: &SteamWebApi,
+    key: &str,
+) -> Result<Vec<String>, String> {
+    let mut ids = vec![];
+    let mut cursor = "*".to_string();
+
+    loop {
+        let response = get_json(
+            client,
+            &api.endpoint("IPublishedFileService/QueryFiles/v1"),
+            &[
+                ("key", key),
+                ("appid", &BARONY_APP_ID.to_string()),
+                ("query_type", QUERY_BY_LAST_UPDATED),
+                ("cursor", &cursor),
+                ("numperpage", &PAGE_SIZE.to_string()),
+                ("ids_only", "true"),
+            ],
+        )
+        .await?;
+
+        let page = response["response"]["publishedfiledetails"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        ids.extend(
+            page.iter()
+                .filter_map(|item| item["publishedfileid"].as_str())
+                .map(|id| id.to_string()),
+        );
+
+        match next_cursor(&response, &cursor) {
+            Some(next) if !page.is_empty() => cursor = next,
+            _ => return Ok(ids),
+        }
+    }
+}
+
+/// The cursor of the next page, if there is one.
+fn next_cursor(response: &Value, current: &str) -> Option<String> {
+    response["response"]["next_cursor"]
+        .as_str()
+        .filter(|next| !next.is_empty() && *next != current)
+        .map(|next| next.to_string())
+}
+
+async fn get_details(
+    client: &Client,
+    api: &SteamWebApi,
+    key: &str,
+    ids: &[String],
+) -> Result<Vec<Value>, String> {
+    let id_params = ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| (format!("publishedfileids[{}]", i), id.as_str()))
+        .collect::<Vec<_>>();
+    let mut params = vec![
+        ("key", key),
+        ("includetags", "true"),
+        ("includevotes", "true"),
+        ("includechildren", "true"),
+        ("includeadditionalpreviews", "true"),
+        ("strip_description_bbcode", "true"),
+    ];
+    params.extend(id_params.iter().map(|(name, id)| (name.as_str(), *id)));
+
+    let response = get_json(
+        client,
+        &api.endpoint("IPublishedFileService/GetDetails/v1"),
+        &params,
+    )
+    .await?;
+
+    Ok(response["response"]["publishedfiledetails"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|details| details["consumer_appid"].as_u64() == Some(BARONY_APP_ID))
+        .collect())
+}
+
+/// Replaces the steam id of the creators by their display name, like the catalog did
+/// before.
+async fn resolve_creator_names(
+    client: &Client,
+    api: &SteamWebApi,
+    key: &str,
+    entries: &mut [Value],
+) -> Result<(), String> {
+    let mut steam_ids = entries
+        .iter()
+        .filter_map(|entry| entry["creator"].as_str())
+        .filter(|creator| !creator.is_empty())
+        .map(|creator| creator.to_string())
+        .collect::<Vec<_>>();
+    steam_ids.sort();
+    steam_ids.dedup();
+
+    let mut names = HashMap::new();
+    for chunk in steam_ids.chunks(PAGE_SIZE) {
+        let response = get_json(
+            client,
+            &api.endpoint("ISteamUser/GetPlayerSummaries/v2"),
+            &[("key", key), ("steamids", &chunk.join(","))],
+        )
+        .await?;
+
+        for player in response["response"]["players"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default()
+        {
+            if let (Some(id), Some(name)) =
+                (player["steamid"].as_str(), player["personaname"].as_str())
+            {
+                names.insert(id.to_string(), name.to_string());
+            }
+        }
+    }
+
+    for entry in entries {
+        let name = entry["creator"]
+            .as_str()
+            .and_then(|creator| names.get(creator))
+            .cloned()
+            .unwrap_or_default();
+        entry["creator"] = json!(name);
+    }
+
+    Ok(())
+}
+
+/// GETs the JSON at `url`, waiting and retrying while Steam answers with a rate limit or
+/// server error.
+async fn get_json(client: &Client, url: &str, params: &[(&str, &str)]) -> Result<Value, String> {
+    let mut attempt = 0;
+
+    loop {
+        tokio::time::sleep(REQUEST_DELAY).await;
+
+        let response = client
+            .get(url)
+            .query(params)
+            .send()
+            .await
+            .map_err(|err| err.to_string())?;
+        let status = response.status();
+
+        if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+            attempt += 1;
+            if attempt > MAX_RETRIES {
+                return Err(format!("{} answered {} too many times", url, status));
+            }
+
+            let wait = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok())
+                .map(Duration::from_secs)
+                .unwrap_or_else(|| Duration::from_secs(2u64.pow(attempt)));
+            eprintln!("Steam answered {}, retrying in {}s", status, wait.as_secs());
+            tokio::time::sleep(wait).await;
+            continue;
+        }
+
+        return response
+            .error_for_status()
+            .map_err(|err| err.to_string())?
+            .json::<Value>()
+            .await
+            .map_err(|err| err.to_string());
+    }
+}
+
+/// The catalog entries in the file, or none when it doesn't exist yet.
+pub fn read_catalog_file(path: &Path) -> io::Result<Vec<Value>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(err),
+    }
+}
+
+pub fn write_catalog_file(path: &Path, entries: &[Value]) -> io::Result<()> {
+    fs::write(path, serde_json::to_string(entries)?)
+}
+
+/// Adds the entry at the end of the changelog file, creating it if needed.
+pub fn append_changelog(path: &Path, entry: ChangelogEntry) -> io::Result<()> {
+    let mut changelog: Vec<ChangelogEntry> = match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content)?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => vec![],
+        Err(err) => return Err(err),
+    };
+    changelog.push(entry);
+    fs::write(path, serde_json::to_string_pretty(&changelog)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::Infallible, net::SocketAddr};
+
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Request, Response, Server,
+    };
+    use reqwest::Url;
+
+    use super::*;
+
+    /// Items of the fake workshop: id and last update, newest first.
+    const WORKSHOP: &[(&str, i64)] = &[("1", 300), ("4", 250), ("2", 100), ("5", 50)];
+
+    fn details(id: &str, time_updated: i64) -> Value {
+        json!({
+            "result": 1,
+            "publishedfileid": id,
+            "consumer_appid": BARONY_APP_ID,
+            "title": format!("Mod {}", id),
+            "time_created": 10,
+            "time_updated": time_updated,
+            "creator": "76561",
+            "vote_data": { "votes_up": 1, "votes_down": 0 },
+        })
+    }
+
+    /// Answers like the Steam Web API, two items per page.
+    async fn fake_steam(request: Request<Body>) -> Result<Response<Body>, Infallible> {
+        let url = Url::parse(&format!("http://steam{}", request.uri())).unwrap();
+        let params = url.query_pairs().into_owned().collect::<HashMap<_, _>>();
+
+        let response = match url.path() {
+            "/IPublishedFileService/QueryFiles/v1/" => {
+                let (page, next_cursor) = match params["cursor"].as_str() {
+                    "*" => (&WORKSHOP[..2], "page2"),
+                    // Steam repeats the cursor of the last page
+                    _ => (&WORKSHOP[2..], "page2"),
+                };
+                let items = page
+                    .iter()
+                    .map(|(id, time_updated)| match params.get("ids_only") {
+                        Some(_) => json!({ "publishedfileid": id }),
+                        None => details(id, *time_updated),
+                    })
+                    .collect::<Vec<_>>();
+                json!({ "response": { "publishedfiledetails": items, "next_cursor": next_cursor } })
+            }
+            "/IPublishedFileService/GetDetails/v1/" => {
+                let items = WORKSHOP
+                    .iter()
+                    .filter(|(id, _)| params.values().any(|value| value == id))
+                    .map(|(id, time_updated)| details(id, *time_updated))
+                    .collect::<Vec<_>>();
+                json!({ "response": { "publishedfiledetails": items } })
+            }
+            "/ISteamUser/GetPlayerSummaries/v2/" => json!({
+                "response": { "players": [{ "steamid": "76561", "personaname": "Alice" }] }
+            }),
+            _ => json!({}),
+        };
+
+        Ok(Response::new(Body::from(response.to_string())))
+    }
+
+    fn serve_fake_steam() -> SocketAddr {
+        let make_service =
+            make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(fake_steam)) });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let address = server.local_addr();
+        tokio::spawn(server);
+        address
+    }
+
+    fn ids(entries: &[Value]) -> Vec<&str> {
+        entries
+            .iter()
+            .filter_map(|entry| entry["id"].as_str())
+            .collect()
+    }
+
+    fn changed_ids(items: &[ChangedItem]) -> Vec<&str> {
+        items.iter().map(|item| item.id.as_str()).collect()
+    }
+
+    #[test]
+    fn merges_the_updated_items_into_the_previous_catalog() {
+        let previous = vec![
+            catalog_entry(&details("1", 100)),
+            catalog_entry(&details("2", 100)),
+            catalog_entry(&details("3", 100)),
+        ];
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (catalog, changelog) = runtime
+            .block_on(async {
+                let address = serve_fake_steam();
+                let api =
+                    SteamWebApi::new(Some(format!("http://{}", address)), Some("key".to_string()));
+                build_catalog(Client::new(), api, previous, false).await
+            })
+            .unwrap();
+
+        // "5" was made public with an old update date, "3" left the workshop
+        assert_eq!(ids(&catalog), ["1", "2", "4", "5"]);
+        assert_eq!(changed_ids(&changelog.added), ["4", "5"]);
+        assert_eq!(changed_ids(&changelog.updated), ["1"]);
+        assert_eq!(changed_ids(&changelog.removed), ["3"]);
+        assert_eq!(catalog[0]["time_updated"], 300);
+        assert_eq!(catalog[0]["creator"], "Alice");
+    }
+
+    #[test]
+    fn refuses_to_drop_most_of_the_catalog() {
+        let previous = (1..=10)
+            .map(|id| catalog_entry(&details(&id.to_string(), 100)))
+            .collect::<Vec<_>>();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let result = runtime.block_on(async {
+            let address = serve_fake_steam();
+            let api =
+                SteamWebApi::new(Some(format!("http://{}", address)), Some("key".to_string()));
+            build_catalog(Client::new(), api, previous, false).await
+        });
+
+        // The fake workshop only lists 4 of them
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn refuses_to_build_without_a_key() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let result = runtime.block_on(build_catalog(
+            Client::new(),
+            SteamWebApi::default(),
+            vec![],
+            false,
+        ));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn stops_paging_at_the_last_cursor() {
+        let response = |cursor: &str| json!({ "response": { "next_cursor": cursor } });
+
+        assert_eq!(next_cursor(&response("abc"), "*").as_deref(), Some("abc"));
+        assert_eq!(next_cursor(&response("abc"), "abc"), None);
+        assert_eq!(next_cursor(&response(""), "*"), None);
+        assert_eq!(next_cursor(&json!({}), "*"), None);
+    }
+}
@@ -0,0 +1,349 @@
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+};
+
+use reqwest::Client;
+
+use crate::{
+    archive_cache::{cache_archive, cached_archive},
+    catalog_builder::{append_changelog, build_catalog, read_catalog_file, write_catalog_file},
+    data::{InstalledMod, SteamWorkshopMod},
+    downloader_api::download_workshop_item,
+    filesystem::{self, barony_dir_valid},
+    lockfile::{create_lock_file, read_lock_file, verify_lock_file, write_lock_file},
+    modpack::read_modpack,
+    reconcile::{plan, Plan},
+    steam_api::{get_barony_workshop_mods, SteamWebApi},
+    versions::{install_version, store_version},
+};
+
+static USAGE: &str = "Usage:
+    barony-mod-manager plan <manifest> [--installation <name>] [--barony-dir <dir>] [--prune]
+    barony-mod-manager apply <manifest> [--installation <name>] [--barony-dir <dir>] [--prune]
+    barony-mod-manager lock <lock file> [--installation <name>] [--barony-dir <dir>]
+    barony-mod-manager verify <lock file> [--installation <name>] [--barony-dir <dir>]
+    barony-mod-manager catalog <mods.json> [--changelog <file>] [--full] [--api-url <url>]
+
+Commands:
+    plan     Show what would change in the mods folder to match the manifest
+    apply    Install, update and remove mods so the mods folder matches the manifest
+    lock     Write the exact versions and content hashes of the installed mods
+    verify   Report the installed mods that differ from the lock file
+    catalog  Update the mods catalog from the Steam Web API, with the key in STEAM_API_KEY
+
+Options:
+    --installation <name> Installation set in the app, defaults to the one in use
+    --barony-dir <dir>    Barony directory, defaults to the one of the installation
+    --prune               Also remove the tracked mods that are not in the manifest
+    --changelog <file>    Where to add the catalog changes, defaults to changelog.json
+                          next to the catalog
+    --full                Fetch every workshop item instead of the updated ones
+    --api-url <url>       Steam Web API address, defaults to the one set in the app
+
+Running without arguments opens the mod manager.";
+
+struct Options {
+    command: String,
+    /// The manifest, or the lock file for `lock` and `verify`
+    file: PathBuf,
+    /// Name of the installation the installed mods are recorded for
+    installation: String,
+    barony_dir: String,
+    prune: bool,
+}
+
+/// Runs the command line interface with the program arguments, not including the
+/// program name.
+pub fn run(args: Vec<String>) -> Result<(), String> {
+    if args.first().map(String::as_str) == Some("catalog") {
+        let options = parse_catalog_args(args)?;
+        let runtime = tokio::runtime::Runtime::new().map_err(|err| err.to_string())?;
+        return runtime.block_on(update_catalog(options));
+    }
+
+    let options = parse_args(args)?;
+
+    match options.command.as_str() {
+        "lock" => lock(&options),
+        "verify" => verify(&options),
+        _ => {
+            let runtime = tokio::runtime::Runtime::new().map_err(|err| err.to_string())?;
+            runtime.block_on(execute(options))
+        }
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Result<Options, String> {
+    let mut args = args.into_iter();
+    let command = match args.next() {
+        Some(command) if ["plan", "apply", "lock", "verify"].contains(&command.as_str()) => command,
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let mut file = None;
+    let mut installation = None;
+    let mut barony_dir = None;
+    let mut prune = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--prune" => prune = true,
+            "--installation" => installation = args.next(),
+            "--barony-dir" => barony_dir = args.next(),
+            _ if file.is_none() && !arg.starts_with("--") => file = Some(arg),
+            _ => return Err(format!("Unexpected argument {}\n\n{}", arg, USAGE)),
+        }
+    }
+
+    let settings = filesystem::load_persisted_settings();
+    let installation = match installation {
+        Some(name) => settings
+            .installations
+            .iter()
+            .find(|installation| installation.name == name)
+            .ok_or_else(|| format!("No installation named {}", name))?,
+        None => settings
+            .installation()
+            .ok_or_else(|| "No installation set in the app".to_string())?,
+    };
+
+    let barony_dir = barony_dir.unwrap_or_else(|| installation.barony_directory_path.clone());
+    if barony_dir.is_empty() {
+        return Err("No Barony directory given or set in the app".to_string());
+    }
+
+    if !barony_dir_valid(&barony_dir) {
+        return Err(format!("{} is not a valid Barony directory", barony_dir));
+    }
+
+    Ok(Options {
+        command,
+        file: PathBuf::from(file.ok_or_else(|| USAGE.to_string())?),
+        installation: installation.name.clone(),
+        barony_dir,
+        prune,
+    })
+}
+
+struct CatalogOptions {
+    catalog: PathBuf,
+    changelog: PathBuf,
+    full: bool,
+    api: SteamWebApi,
+}
+
+fn parse_catalog_args(args: Vec<String>) -> Result<CatalogOptions, String> {
+    let mut catalog = None;
+    let mut changelog = None;
+    let mut full = false;
+    let mut api_url = None;
+
+    let mut args = args.into_iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--full" => full = true,
+            "--changelog" => changelog = args.next().map(PathBuf::from),
+            "--api-url" => api_url = args.next(),
+            _ if catalog.is_none() && !arg.starts_with("--") => catalog = Some(PathBuf::from(arg)),
+            _ => return Err(format!("Unexpected argument {}\n\n{}", arg, USAGE)),
+        }
+    }
+
+    let catalog = catalog.ok_or_else(|| USAGE.to_string())?;
+    let changelog = changelog.unwrap_or_else(|| {
+        catalog
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join("changelog.json")
+    });
+
+    // The API settings of the installation in use in the app
+    let settings = filesystem::load_persisted_settings();
+    let installation = settings.installation();
+    let api = SteamWebApi::new(
+        api_url
+            .or_else(|| installation.and_then(|installation| installation.steam_api_url.clone())),
+        env::var("STEAM_API_KEY")
+            .ok()
+            .or_else(|| installation.and_then(|installation| installation.steam_api_key.clone())),
+    );
+
+    Ok(CatalogOptions {
+        catalog,
+        changelog,
+        full,
+        api,
+    })
+}
+
+async fn update_catalog(options: CatalogOptions) -> Result<(), String> {
+    let previous = read_catalog_file(&options.catalog)
+        .map_err(|err| format!("Could not read {}: {}", options.catalog.display(), err))?;
+    let (catalog, changes) =
+        build_catalog(Client::new(), options.api.clone(), previous, options.full).await?;
+
+    write_catalog_file(&options.catalog, &catalog)
+        .map_err(|err| format!("Could not write {}: {}", options.catalog.display(), err))?;
+    println!(
+        "{} mods: {} added, {} updated, {} removed",
+        catalog.len(),
+        changes.added.len(),
+        changes.updated.len(),
+        changes.removed.len()
+    );
+
+    if !changes.is_empty() {
+        append_changelog(&options.changelog, changes)
+            .map_err(|err| format!("Could not write {}: {}", options.changelog.display(), err))?;
+    }
+    Ok(())
+}
+
+async fn execute(options: Options) -> Result<(), String> {
+    let manifest = read_modpack(&options.file)
+        .map_err(|err| format!("Could not read {}: {}", options.file.display(), err))?;
+
+    let client = Client::new();
+    let catalog = get_barony_workshop_mods(client.clone())
+        .await
+        .map_err(|err| format!("Could not fetch the mods catalog: {}", err))?;
+
+    let mut installed = filesystem::load_installed_mods(&options.installation);
+    let barony_dir = options.barony_dir.clone();
+    let plan = plan(
+        &manifest,
+        &catalog.mods,
+        &installed,
+        |folder| filesystem::is_mod_folder_present(&barony_dir, folder),
+        options.prune,
+    );
+
+    println!("{}", plan);
+
+    if options.command != "apply" || !plan.has_changes() {
+        return Ok(());
+    }
+
+    let result = apply(
+        &options.installation,
+        &options.barony_dir,
+        &plan,
+        &catalog.mods,
+        &mut installed,
+        client,
+    )
+    .await;
+    filesystem::persist_installed_mods(&options.installation, &installed)
+        .map_err(|err| format!("Could not save the installed mods: {}", err))?;
+    result
+}
+
+fn lock(options: &Options) -> Result<(), String> {
+    let installed = filesystem::load_installed_mods(&options.installation);
+    let lock = create_lock_file(&options.barony_dir, &installed)
+        .map_err(|err| format!("Could not hash the installed mods: {}", err))?;
+    write_lock_file(&options.file, &lock)
+        .map_err(|err| format!("Could not write {}: {}", options.file.display(), err))?;
+
+    println!(
+        "Locked {} mods to {}",
+        lock.mods.len(),
+        options.file.display()
+    );
+    Ok(())
+}
+
+fn verify(options: &Options) -> Result<(), String> {
+    let lock = read_lock_file(&options.file)
+        .map_err(|err| format!("Could not read {}: {}", options.file.display(), err))?;
+    let installed = filesystem::load_installed_mods(&options.installation);
+    let drifts = verify_lock_file(&lock, &options.barony_dir, &installed)
+        .map_err(|err| format!("Could not hash the installed mods: {}", err))?;
+
+    if drifts.is_empty() {
+        println!("All {} mods match the lock file", lock.mods.len());
+        return Ok(());
+    }
+
+    for drift in &drifts {
+        println!("{}", drift);
+    }
+    Err(format!("{} mods differ from the lock file", drifts.len()))
+}
+
+async fn apply(
+    installation: &str,
+    barony_dir: &str,
+    plan: &Plan,
+    catalog: &[SteamWorkshopMod],
+    installed: &mut HashMap<String, InstalledMod>,
+    client: Client,
+) -> Result<(), String> {
+    // Checked before anything changes, so a broken dependency doesn't leave it half done
+    let install_order = plan.install_order(catalog)?;
+
+    for item in &plan.remove {
+        if let Some(installed_mod) = installed.get(&item.id) {
+            store_version(installation, barony_dir, &item.id, installed_mod)
+                .map_err(|err| format!("Could not keep the version of {}: {}", item.title, err))?;
+            filesystem::delete_mod_from_disk(barony_dir, &installed_mod.folder)
+                .map_err(|err| format!("Could not remove {}: {}", item.title, err))?;
+            // Only forget the mod once it's really gone, so a failure leaves it tracked
+            installed.remove(&item.id);
+            println!("Removed {}", item.title);
+        }
+    }
+
+    for item in install_order {
+        let workshop = match catalog.iter().find(|mod_| mod_.id == item.id) {
+            Some(workshop) => workshop.clone(),
+            None => continue,
+        };
+        let id = item
+            .id
+            .parse::<u64>()
+            .map_err(|_| format!("{} is not a valid workshop id", item.id))?;
+
+        let zip_bytes = match cached_archive(&item.id, workshop.time_updated) {
+            Some(zip_bytes) => zip_bytes,
+            None => {
+                println!("Downloading {}...", item.title);
+                let zip_bytes = download_workshop_item(client.clone(), id)
+                    .await
+                    .map_err(|err| format!("Could not download {}: {}", item.title, err))?;
+                if filesystem::validate_mod_archive(&zip_bytes).is_ok() {
+                    if let Err(err) = cache_archive(&item.id, workshop.time_updated, &zip_bytes) {
+                        println!("Could not cache the archive of {}: {}", item.title, err);
+                    }
+                }
+                zip_bytes
+            }
+        };
+
+        let folder = installed
+            .get(&item.id)
+            .map(|installed_mod| installed_mod.folder.clone())
+            .unwrap_or_else(|| filesystem::mod_folder_name(&workshop.title));
+
+        install_version(
+            installation,
+            barony_dir,
+            &item.id,
+            installed.get(&item.id),
+            &folder,
+            zip_bytes,
+        )
+        .map_err(|err| format!("Could not install {}: {}", item.title, err))?;
+        match installed.get_mut(&item.id) {
+            Some(installed_mod) => installed_mod.set_installed(workshop),
+            None => {
+                installed.insert(item.id.clone(), InstalledMod::new(folder, workshop));
+            }
+        }
+        println!("Installed {}", item.title);
+    }
+
+    Ok(())
+}
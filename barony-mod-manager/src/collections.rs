@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+
+/// A workshop collection, with its items in the collection order.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WorkshopCollection {
+    pub id: String,
+    pub title: String,
+    pub items: Vec<String>,
+    /// Items installed because they were in the collection, the only ones syncing it
+    /// removes when they leave it
+    #[serde(default)]
+    pub installed: Vec<String>,
+}
+
+/// What changed in a collection since it was last synced.
+#[derive(Debug, Clone, Default)]
+pub struct CollectionChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Compares the fetched collection with the tracked one. Every item is new for a
+/// collection that is not tracked yet.
+pub fn collection_changes(
+    tracked: Option<&WorkshopCollection>,
+    fetched: &WorkshopCollection,
+) -> CollectionChanges {
+    let tracked_items = tracked.map_or(&[][..], |tracked| &tracked.items[..]);
+
+    CollectionChanges {
+        added: fetched
+            .items
+            .iter()
+            .filter(|item| !tracked_items.contains(item))
+            .cloned()
+            .collect(),
+        removed: tracked_items
+            .iter()
+            .filter(|item| !fetched.items.contains(item))
+            .cloned()
+            .collect(),
+    }
+}
+
+/// Items that left the collection `id`, were installed by it and no other tracked
+/// collection has, so they can be removed.
+pub fn items_to_remove(
+    id: &str,
+    removed: &[String],
+    collections: &[WorkshopCollection],
+) -> Vec<String> {
+    let installed = collections
+        .iter()
+        .find(|collection| collection.id == id)
+        .map_or(&[][..], |collection| &collection.installed[..]);
+
+    removed
+        .iter()
+        .filter(|item| installed.contains(item))
+        .filter(|item| {
+            !collections
+                .iter()
+                .filter(|collection| collection.id != id)
+                .any(|collection| collection.items.contains(item))
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collection(id: &str, items: &[&str]) -> WorkshopCollection {
+        WorkshopCollection {
+            id: id.to_string(),
+            title: format!("Collection {}", id),
+            items: items.iter().map(|item| item.to_string()).collect(),
+            installed: items.iter().map(|item| item.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn every_item_of_a_new_collection_is_added() {
+        let changes = collection_changes(None, &collection("10", &["1", "2"]));
+
+        assert_eq!(changes.added, ["1", "2"]);
+        assert!(changes.removed.is_empty());
+    }
+
+    #[test]
+    fn finds_added_and_removed_items() {
+        let tracked = collection("10", &["1", "2", "3"]);
+        let changes = collection_changes(Some(&tracked), &collection("10", &["3", "1", "4"]));
+
+        assert_eq!(changes.added, ["4"]);
+        assert_eq!(changes.removed, ["2"]);
+    }
+
+    #[test]
+    fn keeps_items_other_collections_have() {
+        let collections = [collection("10", &["1", "2", "3"]), collection("20", &["2"])];
+        let removed = ["1".to_string(), "2".to_string()];
+
+        // The collection being synced doesn't keep its own items
+        assert_eq!(items_to_remove("10", &removed, &collections), ["1"]);
+        assert!(items_to_remove("20", &removed, &collections).is_empty());
+    }
+
+    #[test]
+    fn keeps_items_installed_before_the_collection_had_them() {
+        let mut tracked = collection("10", &["1", "2"]);
+        tracked.installed = vec!["1".to_string()];
+        let removed = ["1".to_string(), "2".to_string()];
+
+        assert_eq!(items_to_remove("10", &removed, &[tracked]), ["1"]);
+    }
+}
@@ -1,26 +1,157 @@
 use std::fmt::{self, Display};
 
-use chrono::{DateTime, Utc};
-use iced::button;
+use chrono::{DateTime, TimeZone, Utc};
 use iced::image::Handle;
+use iced::{button, text_input};
 use serde::{Deserialize, Serialize};
 use serde_with::formats::Flexible;
 use serde_with::TimestampSeconds;
 
+use crate::collections::WorkshopCollection;
+
 #[derive(Debug, Clone)]
 pub struct BaronyMod {
     pub workshop: SteamWorkshopMod,
+    /// Set when the mod is no longer part of the workshop catalog
+    pub delisted: bool,
+    /// Added by id while not in the catalog yet, so it's not delisted either
+    pub outside_catalog: bool,
     pub image_handle: Handle,
+    pub extra_previews: Vec<Handle>,
     pub download_button: button::State,
+    pub remove_with_dependencies_button: button::State,
+    pub keep_button: button::State,
+    pub backup_button: button::State,
+    pub peer_download_button: button::State,
+    pub refresh_button: button::State,
+    pub watch_button: button::State,
+    pub subscribe_button: button::State,
+    pub pin_button: button::State,
+    pub rollback_button: button::State,
     pub download_status: DownloadStatus,
 }
 
+#[cfg(test)]
+impl BaronyMod {
+    /// A card for the metadata, without any image.
+    pub(crate) fn for_tests(workshop: SteamWorkshopMod, download_status: DownloadStatus) -> Self {
+        BaronyMod {
+            workshop,
+            delisted: false,
+            outside_catalog: false,
+            image_handle: Handle::from_memory(vec![]),
+            extra_previews: vec![],
+            download_button: button::State::new(),
+            remove_with_dependencies_button: button::State::new(),
+            keep_button: button::State::new(),
+            backup_button: button::State::new(),
+            peer_download_button: button::State::new(),
+            refresh_button: button::State::new(),
+            watch_button: button::State::new(),
+            subscribe_button: button::State::new(),
+            pin_button: button::State::new(),
+            rollback_button: button::State::new(),
+            download_status,
+        }
+    }
+}
+
+/// A folder inside `mods/` that doesn't belong to any mod known by the manager, such as
+/// hand-copied mods or the user's own ones.
+#[derive(Debug, Clone)]
+pub struct LocalMod {
+    pub folder: String,
+    pub image_handle: Handle,
+    pub link_input: text_input::State,
+    pub link_value: String,
+    pub link_button: button::State,
+}
+
+/// A workshop collection kept in sync, with its buttons.
+#[derive(Debug, Clone)]
+pub struct TrackedCollection {
+    pub collection: WorkshopCollection,
+    pub sync_button: button::State,
+    pub forget_button: button::State,
+}
+
+impl TrackedCollection {
+    pub fn new(collection: WorkshopCollection) -> TrackedCollection {
+        TrackedCollection {
+            collection,
+            sync_button: button::State::new(),
+            forget_button: button::State::new(),
+        }
+    }
+}
+
+/// What the manager remembers about a mod it installed, so the mod can still be
+/// managed after it disappears from the workshop catalog.
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InstalledMod {
+    /// Name of the mod folder inside `mods/`
+    pub folder: String,
+    /// Last known catalog metadata
+    pub workshop: SteamWorkshopMod,
+    /// The user chose to keep the mod after it was delisted
+    #[serde(default)]
+    pub keep_delisted: bool,
+    /// `time_updated` of the version on disk
+    #[serde_as(as = "Option<TimestampSeconds<String, Flexible>>")]
+    #[serde(default)]
+    pub installed_version: Option<DateTime<Utc>>,
+    /// Updated automatically when the workshop has a newer version
+    #[serde(default)]
+    pub subscribed: bool,
+    /// Never updated automatically, even when subscribed
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+impl InstalledMod {
+    pub fn new(folder: String, workshop: SteamWorkshopMod) -> InstalledMod {
+        InstalledMod {
+            folder,
+            installed_version: Some(workshop.time_updated),
+            workshop,
+            keep_delisted: false,
+            subscribed: false,
+            pinned: false,
+        }
+    }
+
+    /// Records that this version of the mod is now on disk, keeping the user's choices.
+    pub fn set_installed(&mut self, workshop: SteamWorkshopMod) {
+        self.installed_version = Some(workshop.time_updated);
+        self.workshop = workshop;
+    }
+
+    /// Should be updated without asking.
+    pub fn wants_auto_update(&self) -> bool {
+        self.subscribed && !self.pinned && self.has_update()
+    }
+
+    /// Installs made before versions were tracked are assumed to be the last known one.
+    pub fn version(&self) -> DateTime<Utc> {
+        self.installed_version.unwrap_or(self.workshop.time_updated)
+    }
+
+    /// The workshop has a newer version than the one on disk.
+    pub fn has_update(&self) -> bool {
+        self.workshop.time_updated > self.version()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DownloadStatus {
     Downloaded,
     NotDownloaded,
+    WaitingForDependencies,
     Preparing,
     Downloading,
+    Removing,
+    RollingBack,
     ErrorOccurred(String),
 }
 
@@ -32,8 +163,13 @@ impl Display for DownloadStatus {
             match self {
                 DownloadStatus::Downloaded => "Downloaded".to_string(),
                 DownloadStatus::NotDownloaded => "Not downloaded".to_string(),
+                DownloadStatus::WaitingForDependencies => {
+                    "Waiting for dependencies...".to_string()
+                }
                 DownloadStatus::Preparing => "Preparing download...".to_string(),
                 DownloadStatus::Downloading => "Downloading...".to_string(),
+                DownloadStatus::Removing => "Removing...".to_string(),
+                DownloadStatus::RollingBack => "Rolling back...".to_string(),
                 DownloadStatus::ErrorOccurred(err) => format!("Error occurred: {}.", err),
             }
         )
@@ -41,8 +177,7 @@ impl Display for DownloadStatus {
 }
 
 #[serde_with::serde_as]
-#[derive(Deserialize, Debug, Clone)]
-// TODO: Figure out about steam workshop dependencies and download mods' dependencies
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct SteamWorkshopMod {
     pub id: String,
     pub title: String,
@@ -56,10 +191,113 @@ pub struct SteamWorkshopMod {
     pub time_created: DateTime<Utc>,
     #[serde_as(as = "TimestampSeconds<String, Flexible>")]
     pub time_updated: DateTime<Utc>,
+
+    // Fields below were added in schema 2, older catalog entries won't have them.
+    #[serde(default = "default_schema")]
+    pub schema: u32,
+    /// Display name of the mod author
+    #[serde(default)]
+    pub creator: String,
+    #[serde(default)]
+    pub favorited: u64,
+    #[serde(default)]
+    pub subscriptions: u64,
+    /// Workshop ids of the items this mod requires
+    #[serde(default)]
+    pub children: Vec<String>,
+    /// Urls of the preview images other than the main one
+    #[serde(default)]
+    pub previews: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl SteamWorkshopMod {
+    /// Metadata for a workshop item we only know the id of.
+    pub fn placeholder(id: &str) -> SteamWorkshopMod {
+        SteamWorkshopMod {
+            id: id.to_string(),
+            title: format!("Workshop item {}", id),
+            file_size: 0,
+            preview_url: String::new(),
+            description: String::new(),
+            tags: vec![],
+            votes: SteamWorkshopVoteData { up: 0, down: 0 },
+            views: 0,
+            time_created: Utc.timestamp(0, 0),
+            time_updated: Utc.timestamp(0, 0),
+            schema: CATALOG_SCHEMA_VERSION,
+            creator: String::new(),
+            favorited: 0,
+            subscriptions: 0,
+            children: vec![],
+            previews: vec![],
+        }
+    }
+
+    /// Takes from `previous` what fresh details from Steam don't have: the creator's name
+    /// is never given, and details fetched without a Steam API key have no votes,
+    /// requirements or extra previews.
+    pub fn keep_missing_details(&mut self, previous: &SteamWorkshopMod) {
+        if self.creator.is_empty() {
+            self.creator = previous.creator.clone();
+        }
+
+        if self.schema < previous.schema {
+            self.schema = previous.schema;
+            self.votes = previous.votes.clone();
+            self.children = previous.children.clone();
+            self.previews = previous.previews.clone();
+        }
+    }
+}
+
+/// The newest catalog schema this version of the app understands
+pub const CATALOG_SCHEMA_VERSION: u32 = 2;
+
+fn default_schema() -> u32 {
+    1
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct SteamWorkshopVoteData {
     pub up: u64,
     pub down: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_details_only_a_key_gives() {
+        let mut previous = SteamWorkshopMod::placeholder("1");
+        previous.creator = "Someone".to_string();
+        previous.votes = SteamWorkshopVoteData { up: 10, down: 2 };
+        previous.children = vec!["2".to_string()];
+        previous.previews = vec!["https://example.com/1.png".to_string()];
+
+        // What a refresh without a key looks like
+        let mut refreshed = SteamWorkshopMod::placeholder("1");
+        refreshed.title = "New title".to_string();
+        refreshed.schema = 1;
+        refreshed.keep_missing_details(&previous);
+
+        assert_eq!(refreshed.title, "New title");
+        assert_eq!(refreshed.creator, "Someone");
+        assert_eq!(refreshed.schema, CATALOG_SCHEMA_VERSION);
+        assert_eq!(refreshed.votes, previous.votes);
+        assert_eq!(refreshed.children, previous.children);
+        assert_eq!(refreshed.previews, previous.previews);
+    }
+
+    #[test]
+    fn trusts_the_details_fetched_with_a_key() {
+        let mut previous = SteamWorkshopMod::placeholder("1");
+        previous.children = vec!["2".to_string()];
+
+        // The requirement was dropped from the workshop item
+        let mut refreshed = SteamWorkshopMod::placeholder("1");
+        refreshed.keep_missing_details(&previous);
+
+        assert!(refreshed.children.is_empty());
+    }
+}
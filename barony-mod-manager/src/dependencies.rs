@@ -0,0 +1,260 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+
+use crate::data::{BaronyMod, DownloadStatus};
+
+/// Maps each workshop id to the ids of the items it requires.
+pub type DependencyGraph = HashMap<String, Vec<String>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencyError {
+    /// The ids forming the cycle, starting and ending with the same id
+    Cycle(Vec<String>),
+    /// Required ids that are not in the catalog
+    Missing(Vec<String>),
+}
+
+impl Display for DependencyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DependencyError::Cycle(ids) => write!(f, "dependency cycle {}", ids.join(" -> ")),
+            DependencyError::Missing(ids) => {
+                write!(f, "required items not found: {}", ids.join(", "))
+            }
+        }
+    }
+}
+
+pub fn dependency_graph(mods: &[BaronyMod]) -> DependencyGraph {
+    mods.iter()
+        .map(|mod_| (mod_.workshop.id.clone(), mod_.workshop.children.clone()))
+        .collect()
+}
+
+/// Walks the whole dependency graph of `root` and returns it in install order, with every
+/// dependency coming before the mods that require it and `root` coming last.
+pub fn resolve_install_order(
+    root: &str,
+    graph: &DependencyGraph,
+) -> Result<Vec<String>, DependencyError> {
+    let mut order = vec![];
+    let mut visited = HashSet::new();
+    let mut path = vec![];
+    let mut missing = vec![];
+
+    visit(
+        root,
+        graph,
+        &mut visited,
+        &mut path,
+        &mut order,
+        &mut missing,
+    )?;
+
+    if missing.is_empty() {
+        Ok(order)
+    } else {
+        Err(DependencyError::Missing(missing))
+    }
+}
+
+fn visit(
+    id: &str,
+    graph: &DependencyGraph,
+    visited: &mut HashSet<String>,
+    path: &mut Vec<String>,
+    order: &mut Vec<String>,
+    missing: &mut Vec<String>,
+) -> Result<(), DependencyError> {
+    if let Some(start) = path.iter().position(|visiting| visiting == id) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(id.to_string());
+        return Err(DependencyError::Cycle(cycle));
+    }
+
+    if visited.contains(id) {
+        return Ok(());
+    }
+
+    let children = match graph.get(id) {
+        Some(children) => children,
+        None => {
+            if !missing.iter().any(|missing_id| missing_id == id) {
+                missing.push(id.to_string());
+            }
+            return Ok(());
+        }
+    };
+
+    path.push(id.to_string());
+    for child in children {
+        visit(child, graph, visited, path, order, missing)?;
+    }
+    path.pop();
+
+    visited.insert(id.to_string());
+    order.push(id.to_string());
+    Ok(())
+}
+
+/// Downloaded mods that directly require `id`.
+pub fn downloaded_dependents(id: &str, mods: &[BaronyMod]) -> Vec<String> {
+    mods.iter()
+        .filter(|mod_| mod_.download_status == DownloadStatus::Downloaded)
+        .filter(|mod_| mod_.workshop.children.iter().any(|child| child == id))
+        .map(|mod_| mod_.workshop.id.clone())
+        .collect()
+}
+
+/// Downloaded mods that have dependencies no other downloaded mod needs, so they can be
+/// removed along with them.
+pub fn removable_with_dependencies(mods: &[BaronyMod]) -> HashSet<String> {
+    mods.iter()
+        .filter(|mod_| mod_.download_status == DownloadStatus::Downloaded)
+        .filter(|mod_| !unused_dependencies(&mod_.workshop.id, mods).is_empty())
+        .map(|mod_| mod_.workshop.id.clone())
+        .collect()
+}
+
+/// Downloaded dependencies of `id`, direct or not, that no other downloaded mod would
+/// need anymore once `id` is removed.
+pub fn unused_dependencies(id: &str, mods: &[BaronyMod]) -> Vec<String> {
+    let graph = dependency_graph(mods);
+    let downloaded = mods
+        .iter()
+        .filter(|mod_| mod_.download_status == DownloadStatus::Downloaded)
+        .map(|mod_| mod_.workshop.id.as_str())
+        .collect::<HashSet<_>>();
+
+    // Every dependency reachable from `id`
+    let mut candidates = vec![];
+    let mut stack = vec![id.to_string()];
+    while let Some(current) = stack.pop() {
+        for child in graph.get(&current).into_iter().flatten() {
+            if child != id && !candidates.contains(child) {
+                candidates.push(child.clone());
+                stack.push(child.clone());
+            }
+        }
+    }
+
+    let mut removing = HashSet::new();
+    removing.insert(id.to_string());
+
+    // Removing a dependency may leave its own dependencies unused, so repeat until
+    // nothing changes
+    loop {
+        let newly_unused = candidates
+            .iter()
+            .filter(|candidate| downloaded.contains(candidate.as_str()))
+            .filter(|candidate| !removing.contains(*candidate))
+            .filter(|candidate| {
+                downloaded_dependents(candidate, mods)
+                    .iter()
+                    .all(|dependent| removing.contains(dependent))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if newly_unused.is_empty() {
+            break;
+        }
+        removing.extend(newly_unused);
+    }
+
+    candidates
+        .into_iter()
+        .filter(|candidate| removing.contains(candidate))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::SteamWorkshopMod;
+
+    fn barony_mod(id: &str, children: &[&str], downloaded: bool) -> BaronyMod {
+        let mut workshop = SteamWorkshopMod::placeholder(id);
+        workshop.children = children.iter().map(|child| child.to_string()).collect();
+
+        let status = if downloaded {
+            DownloadStatus::Downloaded
+        } else {
+            DownloadStatus::NotDownloaded
+        };
+        BaronyMod::for_tests(workshop, status)
+    }
+
+    fn graph(edges: &[(&str, &[&str])]) -> DependencyGraph {
+        edges
+            .iter()
+            .map(|(id, children)| {
+                let children = children.iter().map(|child| child.to_string()).collect();
+                (id.to_string(), children)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn orders_dependencies_before_the_mods_requiring_them() {
+        let graph = graph(&[("a", &["b", "c"]), ("b", &["c"]), ("c", &[])]);
+
+        assert_eq!(resolve_install_order("a", &graph).unwrap(), ["c", "b", "a"]);
+    }
+
+    #[test]
+    fn reports_dependency_cycles() {
+        let graph = graph(&[("a", &["b"]), ("b", &["c"]), ("c", &["b"])]);
+
+        assert_eq!(
+            resolve_install_order("a", &graph),
+            Err(DependencyError::Cycle(vec![
+                "b".to_string(),
+                "c".to_string(),
+                "b".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn reports_every_missing_item_once() {
+        let graph = graph(&[("a", &["x", "b"]), ("b", &["x", "y"])]);
+
+        assert_eq!(
+            resolve_install_order("a", &graph),
+            Err(DependencyError::Missing(vec![
+                "x".to_string(),
+                "y".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn finds_downloaded_dependents() {
+        let mods = [
+            barony_mod("a", &["c"], true),
+            barony_mod("b", &["c"], false),
+            barony_mod("c", &[], true),
+        ];
+
+        assert_eq!(downloaded_dependents("c", &mods), ["a"]);
+    }
+
+    #[test]
+    fn keeps_dependencies_other_mods_still_need() {
+        let mods = [
+            barony_mod("a", &["b", "c"], true),
+            barony_mod("b", &["d"], true),
+            barony_mod("c", &[], true),
+            barony_mod("d", &[], true),
+            barony_mod("e", &["c"], true),
+        ];
+
+        assert_eq!(unused_dependencies("a", &mods), ["b", "d"]);
+        assert!(unused_dependencies("e", &mods).is_empty());
+
+        let removable = removable_with_dependencies(&mods);
+        assert!(removable.contains("a") && removable.contains("b"));
+        assert_eq!(removable.len(), 2);
+    }
+}
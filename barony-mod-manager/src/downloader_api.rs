@@ -8,7 +8,7 @@ use serde_json::{json, Value};
 /// and returns the process UUID if everything went right.
 pub async fn queue_download(
     client: Client,
-    workshop_item_id: u32,
+    workshop_item_id: u64,
 ) -> Result<String, reqwest::Error> {
     let endpoint = "https://backend-02-prd.steamworkshopdownloader.io/api/download/request";
 
@@ -60,3 +60,34 @@ pub async fn download_mod(client: Client, uuid: String) -> Result<Vec<u8>, reqwe
     let content = response.bytes().await?;
     Ok(content.to_vec())
 }
+
+/// How many times `download_workshop_item` asks for the status before giving up.
+const MAX_STATUS_CHECKS: u32 = 240;
+
+/// Runs the whole download process for a workshop item: queues it, waits until it is
+/// prepared and downloads it. Meant for callers that don't need to report each step.
+pub async fn download_workshop_item(
+    client: Client,
+    workshop_item_id: u64,
+) -> Result<Vec<u8>, String> {
+    let uuid = queue_download(client.clone(), workshop_item_id)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let mut checks = 0;
+    while !check_status(client.clone(), uuid.clone())
+        .await
+        .map_err(|err| err.to_string())?
+    {
+        checks += 1;
+        if checks >= MAX_STATUS_CHECKS {
+            return Err("the download was not prepared in time".to_string());
+        }
+        // Don't hammer the service while it prepares the archive
+        async_std::task::sleep(Duration::from_secs(1)).await;
+    }
+
+    download_mod(client, uuid)
+        .await
+        .map_err(|err| err.to_string())
+}
@@ -1,15 +1,154 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::{fs, io, path::Path};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    collections::WorkshopCollection,
+    data::InstalledMod,
+    history::CatalogSnapshot,
+    store, versions,
+    watchlist::{Notification, Watchlist},
+};
+
+/// Name of the installation made from the directory set before there could be several.
+pub static DEFAULT_INSTALLATION: &str = "Default";
+
+/// A Barony directory the mods are managed in, like the Steam or GOG install or a test copy.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Installation {
+    pub name: String,
+    pub barony_directory_path: String,
+    #[serde(default)]
+    pub steam_api_key: Option<String>,
+    /// Overrides the Steam Web API base url
+    #[serde(default)]
+    pub steam_api_url: Option<String>,
+    /// Minutes between catalog refreshes while the app is open, 0 to never refresh
+    #[serde(default)]
+    pub refresh_interval_minutes: Option<u64>,
+}
+
+impl Installation {
+    pub fn new(name: String, barony_directory_path: String) -> Installation {
+        Installation {
+            name,
+            barony_directory_path,
+            steam_api_key: None,
+            steam_api_url: None,
+            refresh_interval_minutes: None,
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct SettingsPersistance {
+    /// Directory set before there could be several installations, only read to migrate it
+    #[serde(default, skip_serializing)]
     pub barony_directory_path: Option<String>,
+    #[serde(default)]
+    pub installations: Vec<Installation>,
+    /// Name of the installation in use
+    #[serde(default)]
+    pub active_installation: String,
+    /// Settings shared by every installation before each had its own, only read to
+    /// migrate them
+    #[serde(default, skip_serializing)]
+    pub steam_api_key: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub steam_api_url: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub refresh_interval_minutes: Option<u64>,
+}
+
+impl SettingsPersistance {
+    /// The installation in use, or the first one if it's gone.
+    pub fn installation(&self) -> Option<&Installation> {
+        self.installations
+            .iter()
+            .find(|installation| installation.name == self.active_installation)
+            .or_else(|| self.installations.first())
+    }
+
+    /// Turns the single directory of older settings into the default installation, and
+    /// moves the files kept for it along.
+    fn migrate_installations(&mut self) {
+        if !self.installations.is_empty() {
+            return;
+        }
+
+        self.installations.push(Installation::new(
+            DEFAULT_INSTALLATION.to_string(),
+            self.barony_directory_path.take().unwrap_or_default(),
+        ));
+        self.active_installation = DEFAULT_INSTALLATION.to_string();
+
+        if let (Some(data_dir), Ok(installation_dir)) =
+            (app_data_dir(), installation_dir(DEFAULT_INSTALLATION))
+        {
+            let _ = fs::create_dir_all(&installation_dir);
+            for file in ["installed.json", "collections.json"] {
+                let _ = fs::rename(data_dir.join(file), installation_dir.join(file));
+            }
+        }
+    }
+
+    /// Gives every installation the API settings that used to be shared, and moves the
+    /// watchlist, notifications and stored versions to the installation in use.
+    fn migrate_shared_settings(&mut self) {
+        let steam_api_key = self.steam_api_key.take();
+        let steam_api_url = self.steam_api_url.take();
+        let refresh_interval_minutes = self.refresh_interval_minutes.take();
+        for installation in &mut self.installations {
+            if installation.steam_api_key.is_none() {
+                installation.steam_api_key = steam_api_key.clone();
+            }
+            if installation.steam_api_url.is_none() {
+                installation.steam_api_url = steam_api_url.clone();
+            }
+            if installation.refresh_interval_minutes.is_none() {
+                installation.refresh_interval_minutes = refresh_interval_minutes;
+            }
+        }
+
+        let active = match self.installation() {
+            Some(installation) => installation.name.clone(),
+            None => return,
+        };
+        if let (Some(data_dir), Ok(installation_dir)) = (app_data_dir(), installation_dir(&active))
+        {
+            let _ = fs::create_dir_all(&installation_dir);
+            for file in ["watchlist.json", "notifications.json"] {
+                if !installation_dir.join(file).exists() {
+                    let _ = fs::rename(data_dir.join(file), installation_dir.join(file));
+                }
+            }
+        }
+        if let Err(err) = versions::migrate_shared_versions(&active) {
+            println!("Could not move the stored versions: {}", err);
+        }
+    }
+}
+
+/// Directory where the mod manager keeps its own files.
+pub fn app_data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|user_data_dir| user_data_dir.join("barony-mod-manager"))
+}
+
+/// Directory of the files kept separately for each installation.
+pub fn installation_dir(installation: &str) -> io::Result<PathBuf> {
+    app_data_dir()
+        .map(|dir| dir.join("installations").join(clean_filename(installation)))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))
 }
 
 pub fn persist_settings(settings: SettingsPersistance) {
     // TODO: Create on_exit hook and run this inside it
-    if let Some(user_data_dir) = dirs::data_dir() {
-        let mod_manager_data_dir = user_data_dir.join("barony-mod-manager");
+    if let Some(mod_manager_data_dir) = app_data_dir() {
         fs::create_dir_all(&mod_manager_data_dir).unwrap();
         let json = serde_json::to_string(&settings).unwrap();
         fs::write(mod_manager_data_dir.join("settings.json"), json).unwrap();
@@ -29,10 +168,14 @@ pub fn barony_dir_valid(dir: &str) -> bool {
 pub fn load_persisted_settings() -> SettingsPersistance {
     let mut settings = SettingsPersistance {
         barony_directory_path: None,
+        installations: vec![],
+        active_installation: String::new(),
+        steam_api_key: None,
+        steam_api_url: None,
+        refresh_interval_minutes: None,
     };
 
-    if let Some(user_data_dir) = dirs::data_dir() {
-        let mod_manager_data_dir = user_data_dir.join("barony-mod-manager");
+    if let Some(mod_manager_data_dir) = app_data_dir() {
         match fs::read_to_string(mod_manager_data_dir.join("settings.json")) {
             Ok(content) => {
                 let content: SettingsPersistance = serde_json::from_str(content.as_str()).unwrap();
@@ -43,9 +186,258 @@ pub fn load_persisted_settings() -> SettingsPersistance {
         }
     }
 
+    settings.migrate_installations();
+    settings.migrate_shared_settings();
     settings
 }
 
+/// Installed mods of the installation by workshop id.
+pub fn load_installed_mods(installation: &str) -> HashMap<String, InstalledMod> {
+    installation_dir(installation)
+        .and_then(|dir| fs::read_to_string(dir.join("installed.json")))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn persist_installed_mods(
+    installation: &str,
+    installed: &HashMap<String, InstalledMod>,
+) -> io::Result<()> {
+    let dir = installation_dir(installation)?;
+    fs::create_dir_all(&dir)?;
+    let json = serde_json::to_string(installed)?;
+    fs::write(dir.join("installed.json"), json)
+}
+
+/// Workshop collections kept in sync with the installation.
+pub fn load_collections(installation: &str) -> Vec<WorkshopCollection> {
+    installation_dir(installation)
+        .and_then(|dir| fs::read_to_string(dir.join("collections.json")))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn persist_collections(
+    installation: &str,
+    collections: &[WorkshopCollection],
+) -> io::Result<()> {
+    let dir = installation_dir(installation)?;
+    fs::create_dir_all(&dir)?;
+    let json = serde_json::to_string(collections)?;
+    fs::write(dir.join("collections.json"), json)
+}
+
+pub fn load_watchlist(installation: &str) -> Watchlist {
+    installation_dir(installation)
+        .and_then(|dir| fs::read_to_string(dir.join("watchlist.json")))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn persist_watchlist(installation: &str, watchlist: &Watchlist) -> io::Result<()> {
+    let dir = installation_dir(installation)?;
+    fs::create_dir_all(&dir)?;
+    let json = serde_json::to_string(watchlist)?;
+    fs::write(dir.join("watchlist.json"), json)
+}
+
+/// Notifications of the installation not cleared yet, newest first.
+pub fn load_notifications(installation: &str) -> Vec<Notification> {
+    installation_dir(installation)
+        .and_then(|dir| fs::read_to_string(dir.join("notifications.json")))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn persist_notifications(installation: &str, notifications: &[Notification]) -> io::Result<()> {
+    let dir = installation_dir(installation)?;
+    fs::create_dir_all(&dir)?;
+    let json = serde_json::to_string(notifications)?;
+    fs::write(dir.join("notifications.json"), json)
+}
+
+/// Snapshots of the catalogs fetched before, oldest first.
+pub fn load_catalog_history() -> Vec<CatalogSnapshot> {
+    let mut history = app_data_dir()
+        .and_then(|dir| fs::read_dir(dir.join("history")).ok())
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+                .filter_map(|content| serde_json::from_str::<CatalogSnapshot>(&content).ok())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    history.sort_by_key(|snapshot| snapshot.time);
+    history
+}
+
+pub fn persist_catalog_snapshot(snapshot: &CatalogSnapshot) -> io::Result<()> {
+    let history_dir = app_data_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?
+        .join("history");
+    fs::create_dir_all(&history_dir)?;
+    let json = serde_json::to_string(snapshot)?;
+    fs::write(
+        history_dir.join(format!("{}.json", snapshot.time.timestamp())),
+        json,
+    )
+}
+
+/// Deletes the catalog snapshots taken before `time`.
+pub fn prune_catalog_history(time: DateTime<Utc>) -> io::Result<()> {
+    let history_dir = match app_data_dir() {
+        Some(dir) => dir.join("history"),
+        None => return Ok(()),
+    };
+
+    for entry in fs::read_dir(history_dir)? {
+        let path = entry?.path();
+        let taken_at = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<i64>().ok());
+        if taken_at.map_or(false, |taken_at| taken_at < time.timestamp()) {
+            fs::remove_file(path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Names of every folder inside `mods/`.
+pub fn list_mod_folders(barony_path: &str) -> Vec<String> {
+    let mut folders = fs::read_dir(Path::new(barony_path).join("mods/"))
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter(|entry| entry.path().is_dir())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    folders.sort();
+    folders
+}
+
+/// The preview image shipped inside a mod folder, if any.
+pub fn find_mod_preview(barony_path: &str, folder: &str) -> Option<PathBuf> {
+    let mod_path = Path::new(barony_path).join("mods/").join(folder);
+    ["preview.jpg", "preview.png", "preview.jpeg"]
+        .iter()
+        .map(|name| mod_path.join(name))
+        .find(|path| path.is_file())
+}
+
+pub fn is_mod_folder_present(barony_path: &str, folder: &str) -> bool {
+    !folder.is_empty() && Path::new(barony_path).join("mods/").join(folder).is_dir()
+}
+
+/// Copies the mod folder into the app backups directory and returns where it was copied.
+pub fn backup_mod(barony_path: &str, folder: &str) -> io::Result<PathBuf> {
+    let backups_dir = app_data_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?
+        .join("backups");
+    let target = backups_dir.join(format!("{}-{}", folder, Utc::now().format("%Y%m%d%H%M%S")));
+
+    copy_dir(&Path::new(barony_path).join("mods/").join(folder), &target)?;
+    Ok(target)
+}
+
+pub fn copy_dir(source: &Path, target: &Path) -> io::Result<()> {
+    fs::create_dir_all(target)?;
+
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let target_path = target.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target_path)?;
+        } else {
+            fs::copy(entry.path(), target_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// SHA-256 of every file in the mod folder along with its path, so the same extracted
+/// mod gives the same hash on any machine.
+pub fn hash_mod_folder(barony_path: &str, folder: &str) -> io::Result<String> {
+    hash_dir(&Path::new(barony_path).join("mods/").join(folder))
+}
+
+/// Same as `hash_mod_folder`, for a mod folder anywhere.
+pub(crate) fn hash_dir(dir: &Path) -> io::Result<String> {
+    let mut files = vec![];
+    list_files(dir, dir, &mut files)?;
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for relative_path in files {
+        let content = fs::read(dir.join(&relative_path))?;
+        hasher.update(relative_path.as_bytes());
+        hasher.update([0]);
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Zips the mod folder, with the paths relative to the folder like the workshop archives.
+pub fn archive_mod_folder(barony_path: &str, folder: &str) -> io::Result<Vec<u8>> {
+    let mod_path = Path::new(barony_path).join("mods/").join(folder);
+    let files = list_mod_files(barony_path, folder)?;
+
+    let mut archive = zip::ZipWriter::new(io::Cursor::new(vec![]));
+    for relative_path in files {
+        archive.start_file(relative_path.as_str(), zip::write::FileOptions::default())?;
+        io::copy(
+            &mut fs::File::open(mod_path.join(&relative_path))?,
+            &mut archive,
+        )?;
+    }
+
+    Ok(archive.finish()?.into_inner())
+}
+
+/// Paths of the files in the mod folder relative to it, sorted and always separated by `/`.
+pub fn list_mod_files(barony_path: &str, folder: &str) -> io::Result<Vec<String>> {
+    let mod_path = Path::new(barony_path).join("mods/").join(folder);
+    let mut files = vec![];
+    list_files(&mod_path, &mod_path, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+/// Paths of the files under `dir` relative to `root`, always separated by `/`.
+fn list_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            list_files(root, &entry.path(), files)?;
+        } else {
+            let path = entry.path();
+            let relative_path = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push(relative_path);
+        }
+    }
+
+    Ok(())
+}
+
 pub fn is_mod_downloaded(barony_path: &str, mod_title: &str) -> bool {
     if mod_title.is_empty() {
         return false;
@@ -61,31 +453,36 @@ pub fn write_mod_to_disk(
     mod_title: String,
     zip_bytes: Vec<u8>,
 ) -> Result<(), std::io::Error> {
+    validate_mod_archive(&zip_bytes)?;
+
     let mod_title_clean = clean_filename(&mod_title);
-    let mod_folder = Path::new(&barony_path)
-        .join("mods/")
-        .join(format!("{}/", mod_title_clean));
+    let mod_folder = Path::new(&barony_path).join("mods/").join(mod_title_clean);
 
+    store::install_archive(&zip_bytes, &mod_folder)
+}
+
+/// Extracts the archive into the folder, keeping the permissions of the files.
+pub(crate) fn extract_archive(zip_bytes: &[u8], mod_folder: &Path) -> io::Result<()> {
     let cursor = std::io::Cursor::new(zip_bytes);
-    let mut archive = zip::ZipArchive::new(cursor).unwrap();
+    let mut archive = zip::ZipArchive::new(cursor)?;
 
     for i in 0..archive.len() {
-        let mut file = archive.by_index(i).unwrap();
+        let mut file = archive.by_index(i)?;
         let outpath = match file.enclosed_name() {
             Some(path) => mod_folder.join(path.to_owned()),
             None => continue,
         };
 
         if (file.name()).ends_with('/') {
-            fs::create_dir_all(&outpath).unwrap();
+            fs::create_dir_all(&outpath)?;
         } else {
             if let Some(p) = outpath.parent() {
                 if !p.exists() {
-                    fs::create_dir_all(&p).unwrap();
+                    fs::create_dir_all(&p)?;
                 }
             }
-            let mut outfile = fs::File::create(&outpath).unwrap();
-            io::copy(&mut file, &mut outfile).unwrap();
+            let mut outfile = fs::File::create(&outpath)?;
+            io::copy(&mut file, &mut outfile)?;
         }
 
         // Get and Set permissions
@@ -94,7 +491,7 @@ pub fn write_mod_to_disk(
             use std::os::unix::fs::PermissionsExt;
 
             if let Some(mode) = file.unix_mode() {
-                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode)).unwrap();
+                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))?;
             }
         }
     }
@@ -102,10 +499,115 @@ pub fn write_mod_to_disk(
     Ok(())
 }
 
+/// Checks that the bytes are a zip archive with at least one file that can be extracted
+/// inside the mod folder, so a broken download or a random file never reaches `mods/`.
+pub fn validate_mod_archive(zip_bytes: &[u8]) -> io::Result<()> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    let mut archive = zip::ZipArchive::new(io::Cursor::new(zip_bytes))
+        .map_err(|_| invalid("the file is not a valid zip archive"))?;
+
+    let mut has_files = false;
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        if file.enclosed_name().is_none() {
+            return Err(invalid("the archive has files outside of the mod folder"));
+        }
+        has_files |= !file.name().ends_with('/');
+    }
+
+    if has_files {
+        Ok(())
+    } else {
+        Err(invalid("the archive is empty"))
+    }
+}
+
+/// A folder or zip archive offered by the archive chooser.
+#[derive(Debug, Clone)]
+pub struct ChooserEntry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+}
+
+/// Where the archive chooser opens when no archive was picked yet.
+pub fn default_chooser_dir() -> PathBuf {
+    dirs::download_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Folders and zip archives inside `dir`, folders first, without the hidden ones.
+pub fn list_chooser_entries(dir: &Path) -> io::Result<Vec<ChooserEntry>> {
+    let mut entries = fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|entry| {
+            let path = entry.path();
+            let is_dir = path.is_dir();
+            let is_zip = path
+                .extension()
+                .map_or(false, |extension| extension.eq_ignore_ascii_case("zip"));
+            if is_dir || is_zip {
+                Some(ChooserEntry { path, is_dir })
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    entries.sort_by(|a, b| {
+        b.is_dir
+            .cmp(&a.is_dir)
+            .then_with(|| a.path.file_name().cmp(&b.path.file_name()))
+    });
+    Ok(entries)
+}
+
+/// Installs a mod from a zip archive on disk into a folder named after the archive, and
+/// returns the folder name.
+pub fn install_mod_archive(barony_path: String, archive_path: PathBuf) -> io::Result<String> {
+    let is_zip = archive_path
+        .extension()
+        .map_or(false, |extension| extension.eq_ignore_ascii_case("zip"));
+    if !is_zip {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "only zip archives can be installed",
+        ));
+    }
+
+    let folder = archive_path
+        .file_stem()
+        .map(|stem| clean_filename(&stem.to_string_lossy()))
+        .unwrap_or_default();
+    if folder.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the archive has no name",
+        ));
+    }
+    if is_mod_folder_present(&barony_path, &folder) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("a mod named \"{}\" is already installed", folder),
+        ));
+    }
+
+    let zip_bytes = fs::read(&archive_path)?;
+    write_mod_to_disk(barony_path, folder.clone(), zip_bytes)?;
+    Ok(folder)
+}
+
 pub fn delete_mod_from_disk(barony_path: &str, mod_title: &str) -> Result<(), std::io::Error> {
     let foldername = clean_filename(mod_title);
     let mod_path = Path::new(barony_path).join("mods/").join(foldername);
-    std::fs::remove_dir_all(mod_path)
+    store::remove_mod_folder(&mod_path)
+}
+
+/// Name of the folder a mod with this title is written to.
+pub fn mod_folder_name(mod_title: &str) -> String {
+    clean_filename(mod_title)
 }
 
 // This removes invalid filename characters that would make the program fail with
@@ -0,0 +1,210 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, TimestampSeconds};
+
+use crate::data::SteamWorkshopMod;
+
+/// How far back trends are measured.
+pub const TREND_WINDOW_DAYS: i64 = 7;
+/// Snapshots older than this are forgotten.
+pub const HISTORY_DAYS: i64 = 30;
+
+/// What the history keeps of a mod, enough to tell what changed between catalogs.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotMod {
+    pub title: String,
+    #[serde_as(as = "TimestampSeconds<i64>")]
+    pub time_created: DateTime<Utc>,
+    #[serde_as(as = "TimestampSeconds<i64>")]
+    pub time_updated: DateTime<Utc>,
+    pub views: u64,
+    pub votes_up: u64,
+    pub subscriptions: u64,
+}
+
+/// The catalog as it was fetched at some point.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogSnapshot {
+    #[serde_as(as = "TimestampSeconds<i64>")]
+    pub time: DateTime<Utc>,
+    pub mods: HashMap<String, SnapshotMod>,
+}
+
+impl CatalogSnapshot {
+    pub fn new(mods: &[SteamWorkshopMod]) -> CatalogSnapshot {
+        CatalogSnapshot {
+            time: Utc::now(),
+            mods: mods
+                .iter()
+                .map(|mod_| {
+                    let snapshot = SnapshotMod {
+                        title: mod_.title.clone(),
+                        time_created: mod_.time_created,
+                        time_updated: mod_.time_updated,
+                        views: mod_.views,
+                        votes_up: mod_.votes.up,
+                        subscriptions: mod_.subscriptions,
+                    };
+                    (mod_.id.clone(), snapshot)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Mods that appeared or were updated since an older catalog.
+#[derive(Debug, Clone, Default)]
+pub struct WhatsNew {
+    pub new: HashSet<String>,
+    pub updated: HashSet<String>,
+}
+
+impl WhatsNew {
+    pub fn is_empty(&self) -> bool {
+        self.new.is_empty() && self.updated.is_empty()
+    }
+
+    pub fn between(older: &CatalogSnapshot, newer: &CatalogSnapshot) -> WhatsNew {
+        let mut whats_new = WhatsNew::default();
+
+        for (id, mod_) in &newer.mods {
+            match older.mods.get(id) {
+                None => {
+                    whats_new.new.insert(id.clone());
+                }
+                Some(old) if old.time_updated < mod_.time_updated => {
+                    whats_new.updated.insert(id.clone());
+                }
+                Some(_) => {}
+            }
+        }
+
+        whats_new
+    }
+}
+
+/// Growth of a mod between two catalogs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Trend {
+    pub views: i64,
+    pub votes: i64,
+}
+
+/// Growth of every mod in the newest snapshot since the oldest one of the trend window,
+/// or the newest one before it if the window has no other snapshot.
+pub fn trends(history: &[CatalogSnapshot]) -> HashMap<String, Trend> {
+    let (current, older) = match history.split_last() {
+        Some(split) => split,
+        None => return HashMap::new(),
+    };
+
+    let window_start = current.time - Duration::days(TREND_WINDOW_DAYS);
+    let base = match older
+        .iter()
+        .find(|snapshot| snapshot.time >= window_start)
+        .or_else(|| older.last())
+    {
+        Some(base) => base,
+        None => return HashMap::new(),
+    };
+
+    current
+        .mods
+        .iter()
+        .filter_map(|(id, mod_)| {
+            let old = base.mods.get(id)?;
+            let trend = Trend {
+                views: mod_.views as i64 - old.views as i64,
+                votes: mod_.votes_up as i64 - old.votes_up as i64,
+            };
+            Some((id.clone(), trend))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn snapshot(day: i64, mods: &[(&str, i64, u64)]) -> CatalogSnapshot {
+        CatalogSnapshot {
+            time: Utc.timestamp(0, 0) + Duration::days(day),
+            mods: mods
+                .iter()
+                .map(|(id, time_updated, views)| {
+                    let mod_ = SnapshotMod {
+                        title: format!("Mod {}", id),
+                        time_created: Utc.timestamp(0, 0),
+                        time_updated: Utc.timestamp(*time_updated, 0),
+                        views: *views,
+                        votes_up: *views / 10,
+                        subscriptions: 0,
+                    };
+                    (id.to_string(), mod_)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn finds_new_and_updated_mods() {
+        let older = snapshot(0, &[("1", 100, 0), ("2", 100, 0), ("3", 100, 0)]);
+        let newer = snapshot(1, &[("1", 200, 0), ("2", 100, 0), ("4", 100, 0)]);
+        let whats_new = WhatsNew::between(&older, &newer);
+
+        assert_eq!(whats_new.new, ["4".to_string()].iter().cloned().collect());
+        assert_eq!(
+            whats_new.updated,
+            ["1".to_string()].iter().cloned().collect()
+        );
+        assert!(WhatsNew::between(&newer, &newer).is_empty());
+    }
+
+    #[test]
+    fn measures_trends_from_the_start_of_the_window() {
+        let history = [
+            snapshot(0, &[("1", 0, 0)]),
+            snapshot(5, &[("1", 0, 100), ("2", 0, 10)]),
+            snapshot(8, &[("1", 0, 150)]),
+            snapshot(12, &[("1", 0, 400), ("2", 0, 30), ("3", 0, 50)]),
+        ];
+        let trends = trends(&history);
+
+        // Day 5 is the oldest snapshot in the last 7 days
+        assert_eq!(
+            trends["1"],
+            Trend {
+                views: 300,
+                votes: 30
+            }
+        );
+        assert_eq!(
+            trends["2"],
+            Trend {
+                views: 20,
+                votes: 2
+            }
+        );
+        // Mods that weren't there have no trend
+        assert!(!trends.contains_key("3"));
+    }
+
+    #[test]
+    fn falls_back_to_the_newest_snapshot_before_the_window() {
+        let history = [
+            snapshot(0, &[("1", 0, 0)]),
+            snapshot(1, &[("1", 0, 40)]),
+            snapshot(20, &[("1", 0, 100)]),
+        ];
+
+        assert_eq!(trends(&history)["1"].views, 60);
+        assert!(trends(&history[..1]).is_empty());
+        assert!(trends(&[]).is_empty());
+    }
+}
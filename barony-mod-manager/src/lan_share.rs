@@ -0,0 +1,242 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    io,
+    net::{SocketAddr, TcpListener, UdpSocket},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use iced::futures::{channel::oneshot, future};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_with::formats::Flexible;
+use serde_with::TimestampSeconds;
+
+use crate::{data::InstalledMod, filesystem};
+
+/// Port the mods are shared on, unless another instance already uses it.
+pub const SHARE_PORT: u16 = 27190;
+/// Port peers listen on for discovery broadcasts.
+pub const DISCOVERY_PORT: u16 = 27191;
+const DISCOVERY_MESSAGE: &[u8] = b"barony-mod-manager discover";
+
+/// An installed mod as listed by a peer.
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SharedMod {
+    pub id: String,
+    pub title: String,
+    #[serde_as(as = "TimestampSeconds<String, Flexible>")]
+    pub time_updated: DateTime<Utc>,
+}
+
+/// Sockets of a share, bound before serving so the port is known right away.
+pub struct ShareSockets {
+    http: TcpListener,
+    discovery: Option<UdpSocket>,
+}
+
+impl ShareSockets {
+    /// Binds the share port, or any free port when it's taken, e.g. by another instance
+    /// on the same machine. Only the first instance answers discovery broadcasts.
+    pub fn bind() -> io::Result<Self> {
+        let http = TcpListener::bind(("0.0.0.0", SHARE_PORT))
+            .or_else(|_| TcpListener::bind(("0.0.0.0", 0)))?;
+        let discovery = UdpSocket::bind(("0.0.0.0", DISCOVERY_PORT)).ok();
+
+        Ok(ShareSockets { http, discovery })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.http.local_addr().map_or(0, |address| address.port())
+    }
+}
+
+/// Serves `mods`, the installed mods when sharing started, over HTTP until `stop` fires or
+/// its sender is dropped.
+///
+/// - `GET /mods` lists the installed mods as JSON
+/// - `GET /mods/<id>` returns the mod folder as a zip archive
+pub async fn serve(
+    sockets: ShareSockets,
+    barony_path: String,
+    mods: HashMap<String, InstalledMod>,
+    stop: oneshot::Receiver<()>,
+) -> Result<(), String> {
+    let port = sockets.port();
+    let mods = Arc::new(mods);
+    let make_service = make_service_fn(move |_| {
+        let barony_path = barony_path.clone();
+        let mods = mods.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle_request(request, barony_path.clone(), mods.clone())
+            }))
+        }
+    });
+
+    let server = Server::from_tcp(sockets.http)
+        .map_err(|err| err.to_string())?
+        .serve(make_service)
+        .with_graceful_shutdown(async {
+            stop.await.ok();
+        });
+
+    let discovery = match sockets.discovery {
+        Some(socket) => {
+            socket
+                .set_nonblocking(true)
+                .map_err(|err| err.to_string())?;
+            let socket = tokio::net::UdpSocket::from_std(socket).map_err(|err| err.to_string())?;
+            future::Either::Left(answer_discovery(socket, port))
+        }
+        None => future::Either::Right(future::pending()),
+    };
+
+    // Discovery runs forever, so this ends with the server
+    match future::select(Box::pin(server), Box::pin(discovery)).await {
+        future::Either::Left((result, _)) => result.map_err(|err| err.to_string()),
+        future::Either::Right(_) => Ok(()),
+    }
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    barony_path: String,
+    mods: Arc<HashMap<String, InstalledMod>>,
+) -> Result<Response<Body>, Infallible> {
+    let path = request.uri().path().trim_matches('/').to_string();
+    let mut segments = path.split('/');
+
+    let response = match (request.method(), segments.next(), segments.next()) {
+        (&Method::GET, Some("mods"), None) => {
+            // Checking the folders touches the disk, so keep it off the server threads
+            let listing = tokio::task::spawn_blocking(move || {
+                let mut shared = mods
+                    .iter()
+                    .filter(|(_, installed)| {
+                        filesystem::is_mod_folder_present(&barony_path, &installed.folder)
+                    })
+                    .map(|(id, installed)| SharedMod {
+                        id: id.clone(),
+                        title: installed.workshop.title.clone(),
+                        time_updated: installed.version(),
+                    })
+                    .collect::<Vec<_>>();
+                shared.sort_by(|a, b| a.title.cmp(&b.title));
+                serde_json::to_vec(&shared)
+            })
+            .await;
+
+            match listing {
+                Ok(Ok(json)) => Response::builder()
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(json)),
+                _ => status_response(StatusCode::INTERNAL_SERVER_ERROR),
+            }
+        }
+        (&Method::GET, Some("mods"), Some(id)) => {
+            let id = id.to_string();
+            let archive = tokio::task::spawn_blocking(move || {
+                mods.get(&id)
+                    .filter(|installed| {
+                        filesystem::is_mod_folder_present(&barony_path, &installed.folder)
+                    })
+                    .map(|installed| {
+                        filesystem::archive_mod_folder(&barony_path, &installed.folder)
+                    })
+            })
+            .await;
+
+            match archive {
+                Ok(Some(Ok(zip_bytes))) => Response::builder()
+                    .header("Content-Type", "application/zip")
+                    .body(Body::from(zip_bytes)),
+                Ok(None) => status_response(StatusCode::NOT_FOUND),
+                _ => status_response(StatusCode::INTERNAL_SERVER_ERROR),
+            }
+        }
+        _ => status_response(StatusCode::NOT_FOUND),
+    };
+
+    Ok(response.unwrap_or_default())
+}
+
+fn status_response(status: StatusCode) -> hyper::http::Result<Response<Body>> {
+    Response::builder().status(status).body(Body::empty())
+}
+
+/// Replies to every discovery broadcast with the port the mods are shared on.
+async fn answer_discovery(socket: tokio::net::UdpSocket, port: u16) {
+    let mut buffer = [0; 64];
+    while let Ok((len, from)) = socket.recv_from(&mut buffer).await {
+        if &buffer[..len] == DISCOVERY_MESSAGE {
+            socket.send_to(port.to_string().as_bytes(), from).await.ok();
+        }
+    }
+}
+
+/// Broadcasts on the local network and returns the addresses of the peers that answered
+/// within a second. The local machine is asked too, for a second instance.
+pub async fn discover_peers() -> io::Result<Vec<SocketAddr>> {
+    let socket = tokio::net::UdpSocket::bind(("0.0.0.0", 0)).await?;
+    socket.set_broadcast(true)?;
+    socket
+        .send_to(DISCOVERY_MESSAGE, ("255.255.255.255", DISCOVERY_PORT))
+        .await?;
+    socket
+        .send_to(DISCOVERY_MESSAGE, ("127.0.0.1", DISCOVERY_PORT))
+        .await?;
+
+    let mut peers = vec![];
+    let mut buffer = [0; 16];
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(1);
+    while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buffer)).await
+    {
+        let (len, from) = received?;
+        let port = String::from_utf8_lossy(&buffer[..len]).parse::<u16>();
+        if let Ok(port) = port {
+            let peer = SocketAddr::new(from.ip(), port);
+            if !peers.contains(&peer) {
+                peers.push(peer);
+            }
+        }
+    }
+
+    Ok(peers)
+}
+
+/// Lists the mods a peer shares. `address` is `host:port`.
+pub async fn fetch_peer_mods(
+    client: Client,
+    address: String,
+) -> Result<Vec<SharedMod>, reqwest::Error> {
+    client
+        .get(format!("http://{}/mods", address))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+}
+
+/// Downloads the archive of a mod from a peer.
+pub async fn download_from_peer(
+    client: Client,
+    address: String,
+    id: String,
+) -> Result<Vec<u8>, reqwest::Error> {
+    let response = client
+        .get(format!("http://{}/mods/{}", address, id))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(response.bytes().await?.to_vec())
+}
@@ -1,7 +1,22 @@
+pub mod catalog;
+pub mod catalog_builder;
+pub mod cli;
+pub mod collections;
 pub mod data;
+pub mod dependencies;
 pub mod steam_api;
 pub mod downloader_api;
 pub mod filesystem;
+pub mod history;
 pub mod styling;
 pub mod widgets;
 pub mod images;
+pub mod modpack;
+pub mod lockfile;
+pub mod lan_share;
+pub mod bundle;
+pub mod reconcile;
+pub mod watchlist;
+pub mod versions;
+pub mod archive_cache;
+pub mod store;
@@ -0,0 +1,404 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    fs, io,
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_with::formats::Flexible;
+use serde_with::TimestampSeconds;
+use sha2::{Digest, Sha256};
+
+use crate::{data::InstalledMod, filesystem};
+
+/// The exact set of installed mods, down to the content of their folders. Modpacks can
+/// be read as lock files too, they just don't have the content hashes.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LockFile {
+    pub mods: Vec<LockedMod>,
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LockedMod {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub folder: String,
+    #[serde_as(as = "TimestampSeconds<String, Flexible>")]
+    pub time_updated: DateTime<Utc>,
+    /// Hash of the extracted files, see `filesystem::hash_mod_folder`. Empty when unknown
+    #[serde(default)]
+    pub hash: String,
+}
+
+impl LockFile {
+    /// Short code identifying the mod set, the same for everyone running exactly the same
+    /// mods. Looks like `3FA2-9C0D`.
+    pub fn fingerprint(&self) -> String {
+        let mut mods = self.mods.iter().collect::<Vec<_>>();
+        mods.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut hasher = Sha256::new();
+        for locked in mods {
+            hasher.update(
+                format!(
+                    "{}:{}:{}\n",
+                    locked.id,
+                    locked.time_updated.timestamp(),
+                    locked.hash
+                )
+                .as_bytes(),
+            );
+        }
+
+        let hex = format!("{:X}", hasher.finalize());
+        format!("{}-{}", &hex[..4], &hex[4..8])
+    }
+}
+
+/// A difference between the lock file and the installed mods.
+#[derive(Debug, Clone)]
+pub struct Drift {
+    pub id: String,
+    pub title: String,
+    pub kind: DriftKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DriftKind {
+    Missing,
+    Version {
+        expected: DateTime<Utc>,
+        installed: DateTime<Utc>,
+    },
+    /// Same version, but the files were changed since they were extracted
+    Content,
+    /// Installed but not in the lock file
+    Unexpected,
+}
+
+impl Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            DriftKind::Missing => write!(f, "missing {} ({})", self.title, self.id),
+            DriftKind::Version {
+                expected,
+                installed,
+            } => write!(
+                f,
+                "{} ({}) is the version of {} instead of {}",
+                self.title,
+                self.id,
+                installed.format("%Y-%m-%d %H:%M"),
+                expected.format("%Y-%m-%d %H:%M")
+            ),
+            DriftKind::Content => write!(f, "files of {} ({}) differ", self.title, self.id),
+            DriftKind::Unexpected => {
+                write!(f, "{} ({}) is not in the lock file", self.title, self.id)
+            }
+        }
+    }
+}
+
+/// Pins every installed mod whose folder is in the `mods/` directory.
+pub fn create_lock_file(
+    barony_path: &str,
+    installed: &HashMap<String, InstalledMod>,
+) -> io::Result<LockFile> {
+    let mut mods = vec![];
+    for (id, installed_mod) in installed {
+        if !filesystem::is_mod_folder_present(barony_path, &installed_mod.folder) {
+            continue;
+        }
+
+        mods.push(LockedMod {
+            id: id.clone(),
+            title: installed_mod.workshop.title.clone(),
+            folder: installed_mod.folder.clone(),
+            time_updated: installed_mod.version(),
+            hash: filesystem::hash_mod_folder(barony_path, &installed_mod.folder)?,
+        });
+    }
+    mods.sort_by(|a, b| a.title.cmp(&b.title));
+
+    Ok(LockFile { mods })
+}
+
+/// Compares the installed mods against the lock file. No drift means this machine runs
+/// exactly the same mods.
+pub fn verify_lock_file(
+    lock: &LockFile,
+    barony_path: &str,
+    installed: &HashMap<String, InstalledMod>,
+) -> io::Result<Vec<Drift>> {
+    let mut drifts = vec![];
+    let mut drift = |id: &str, title: &str, kind| {
+        drifts.push(Drift {
+            id: id.to_string(),
+            title: title.to_string(),
+            kind,
+        })
+    };
+
+    for locked in &lock.mods {
+        let installed_mod = match installed.get(&locked.id) {
+            Some(installed_mod)
+                if filesystem::is_mod_folder_present(barony_path, &installed_mod.folder) =>
+            {
+                installed_mod
+            }
+            _ => {
+                drift(&locked.id, &locked.title, DriftKind::Missing);
+                continue;
+            }
+        };
+
+        if installed_mod.version() != locked.time_updated {
+            drift(
+                &locked.id,
+                &locked.title,
+                DriftKind::Version {
+                    expected: locked.time_updated,
+                    installed: installed_mod.version(),
+                },
+            );
+        } else if !locked.hash.is_empty()
+            // Modpacks have no hashes, only their versions can be checked
+            && filesystem::hash_mod_folder(barony_path, &installed_mod.folder)? != locked.hash
+        {
+            drift(&locked.id, &locked.title, DriftKind::Content);
+        }
+    }
+
+    let mut unexpected = installed
+        .iter()
+        .filter(|(id, _)| !lock.mods.iter().any(|locked| &locked.id == *id))
+        .filter(|(_, installed_mod)| {
+            filesystem::is_mod_folder_present(barony_path, &installed_mod.folder)
+        })
+        .collect::<Vec<_>>();
+    unexpected.sort_by(|a, b| a.1.workshop.title.cmp(&b.1.workshop.title));
+    for (id, installed_mod) in unexpected {
+        drift(id, &installed_mod.workshop.title, DriftKind::Unexpected);
+    }
+
+    Ok(drifts)
+}
+
+/// A mod that is not the same in two mod lists.
+#[derive(Debug, Clone)]
+pub struct ModDifference {
+    pub id: String,
+    pub title: String,
+    pub mine: Option<DateTime<Utc>>,
+    pub theirs: Option<DateTime<Utc>>,
+    /// Same version on both sides, but the files are not the same
+    pub files_differ: bool,
+}
+
+/// Every mod that is missing on one side, has another version or other files.
+pub fn compare_mod_lists(mine: &LockFile, theirs: &LockFile) -> Vec<ModDifference> {
+    let mut differences = vec![];
+
+    for my_mod in &mine.mods {
+        let their_mod = theirs.mods.iter().find(|locked| locked.id == my_mod.id);
+        let files_differ = their_mod.map_or(false, |their_mod| {
+            !my_mod.hash.is_empty() && !their_mod.hash.is_empty() && my_mod.hash != their_mod.hash
+        });
+
+        if their_mod.map(|locked| locked.time_updated) != Some(my_mod.time_updated) || files_differ
+        {
+            differences.push(ModDifference {
+                id: my_mod.id.clone(),
+                title: my_mod.title.clone(),
+                mine: Some(my_mod.time_updated),
+                theirs: their_mod.map(|locked| locked.time_updated),
+                files_differ,
+            });
+        }
+    }
+
+    for their_mod in &theirs.mods {
+        if !mine.mods.iter().any(|locked| locked.id == their_mod.id) {
+            differences.push(ModDifference {
+                id: their_mod.id.clone(),
+                title: their_mod.title.clone(),
+                mine: None,
+                theirs: Some(their_mod.time_updated),
+                files_differ: false,
+            });
+        }
+    }
+
+    differences.sort_by(|a, b| a.title.cmp(&b.title));
+    differences
+}
+
+pub fn read_lock_file(path: &Path) -> io::Result<LockFile> {
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+pub fn write_lock_file(path: &Path, lock: &LockFile) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(lock)?;
+    fs::write(path, json)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::data::SteamWorkshopMod;
+
+    fn installed_mod(id: &str, time_updated: i64) -> InstalledMod {
+        let mut workshop = SteamWorkshopMod::placeholder(id);
+        workshop.title = format!("Mod {}", id);
+        workshop.time_updated = Utc.timestamp(time_updated, 0);
+        InstalledMod::new(workshop.title.clone(), workshop)
+    }
+
+    fn write_mod(barony_dir: &Path, folder: &str, content: &str) {
+        let mod_dir = barony_dir.join("mods").join(folder);
+        fs::create_dir_all(&mod_dir).unwrap();
+        fs::write(mod_dir.join("data.txt"), content).unwrap();
+    }
+
+    fn kinds(drifts: &[Drift]) -> Vec<(&str, DriftKind)> {
+        drifts
+            .iter()
+            .map(|drift| (drift.id.as_str(), drift.kind.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn verifies_a_fresh_lock_file_without_drift() {
+        let barony_dir = tempfile::tempdir().unwrap();
+        let barony_path = barony_dir.path().to_str().unwrap();
+        write_mod(barony_dir.path(), "Mod 1", "one");
+        let mut installed = HashMap::new();
+        installed.insert("1".to_string(), installed_mod("1", 100));
+        // Tracked but not on disk, so not locked
+        installed.insert("2".to_string(), installed_mod("2", 100));
+
+        let lock = create_lock_file(barony_path, &installed).unwrap();
+        assert_eq!(lock.mods.len(), 1);
+        assert!(verify_lock_file(&lock, barony_path, &installed)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn verifies_a_lock_file_without_hashes_by_version() {
+        let barony_dir = tempfile::tempdir().unwrap();
+        let barony_path = barony_dir.path().to_str().unwrap();
+        write_mod(barony_dir.path(), "Mod 1", "one");
+        let mut installed = HashMap::new();
+        installed.insert("1".to_string(), installed_mod("1", 100));
+
+        let mut lock = create_lock_file(barony_path, &installed).unwrap();
+        lock.mods[0].hash.clear();
+        assert!(verify_lock_file(&lock, barony_path, &installed)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn detects_every_kind_of_drift() {
+        let barony_dir = tempfile::tempdir().unwrap();
+        let barony_path = barony_dir.path().to_str().unwrap();
+        for id in &["1", "2", "3"] {
+            write_mod(barony_dir.path(), &format!("Mod {}", id), id);
+        }
+        let mut installed = HashMap::new();
+        for id in &["1", "2", "3"] {
+            installed.insert(id.to_string(), installed_mod(id, 100));
+        }
+        let lock = create_lock_file(barony_path, &installed).unwrap();
+
+        // "1" is edited, "2" is updated, "3" is deleted and "4" is installed
+        write_mod(barony_dir.path(), "Mod 1", "edited");
+        installed.insert("2".to_string(), installed_mod("2", 200));
+        fs::remove_dir_all(barony_dir.path().join("mods/Mod 3")).unwrap();
+        write_mod(barony_dir.path(), "Mod 4", "four");
+        installed.insert("4".to_string(), installed_mod("4", 100));
+
+        let drifts = verify_lock_file(&lock, barony_path, &installed).unwrap();
+        assert_eq!(
+            kinds(&drifts),
+            [
+                ("1", DriftKind::Content),
+                (
+                    "2",
+                    DriftKind::Version {
+                        expected: Utc.timestamp(100, 0),
+                        installed: Utc.timestamp(200, 0),
+                    }
+                ),
+                ("3", DriftKind::Missing),
+                ("4", DriftKind::Unexpected),
+            ]
+        );
+    }
+
+    #[test]
+    fn fingerprints_ignore_the_order_of_mods() {
+        let locked = |id: &str| LockedMod {
+            id: id.to_string(),
+            title: String::new(),
+            folder: String::new(),
+            time_updated: Utc.timestamp(100, 0),
+            hash: "abc".to_string(),
+        };
+        let lock = LockFile {
+            mods: vec![locked("1"), locked("2")],
+        };
+        let reversed = LockFile {
+            mods: vec![locked("2"), locked("1")],
+        };
+
+        assert_eq!(lock.fingerprint(), reversed.fingerprint());
+        assert_eq!(lock.fingerprint().len(), 9);
+    }
+
+    #[test]
+    fn compares_mod_lists_without_hashes() {
+        let locked = |id: &str, time_updated: i64, hash: &str| LockedMod {
+            id: id.to_string(),
+            title: format!("Mod {}", id),
+            folder: String::new(),
+            time_updated: Utc.timestamp(time_updated, 0),
+            hash: hash.to_string(),
+        };
+        let mine = LockFile {
+            mods: vec![
+                locked("1", 100, "a"),
+                locked("2", 100, "b"),
+                locked("3", 100, "c"),
+                locked("4", 100, "d"),
+            ],
+        };
+        // Modpacks don't have hashes, so "4" is the same
+        let theirs = LockFile {
+            mods: vec![
+                locked("1", 100, "a"),
+                locked("2", 200, "b"),
+                locked("3", 100, "other"),
+                locked("4", 100, ""),
+                locked("5", 100, "e"),
+            ],
+        };
+
+        let differences = compare_mod_lists(&mine, &theirs);
+        let ids = differences
+            .iter()
+            .map(|difference| difference.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["2", "3", "5"]);
+        assert!(differences[1].files_differ);
+        assert_eq!(differences[2].mine, None);
+    }
+}
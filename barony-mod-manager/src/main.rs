@@ -1,31 +1,92 @@
 // Don't show the console when starting the app on Windows
 #![windows_subsystem = "windows"]
 
-use std::{collections::HashSet, time::Duration, vec};
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::PathBuf,
+    time::Duration,
+    vec,
+};
 
 use barony_mod_manager::{
-    data::{BaronyMod, DownloadStatus},
+    archive_cache::{self, CacheStats},
+    bundle::{export_bundle, import_bundle},
+    catalog::{diff_catalog, CatalogDiagnostics},
+    cli,
+    collections::{collection_changes, items_to_remove},
+    data::{
+        BaronyMod, DownloadStatus, InstalledMod, LocalMod, SteamWorkshopMod, TrackedCollection,
+    },
+    dependencies::{
+        dependency_graph, downloaded_dependents, removable_with_dependencies,
+        resolve_install_order, unused_dependencies,
+    },
     downloader_api::{check_status, download_mod, queue_download},
-    filesystem::{self, barony_dir_valid},
+    filesystem::{self, barony_dir_valid, ChooserEntry, Installation, DEFAULT_INSTALLATION},
+    history::{self, CatalogSnapshot, Trend, WhatsNew, HISTORY_DAYS},
     images::build_app_logo,
-    steam_api::{build_barony_mod, get_barony_workshop_mods},
+    lan_share::{self, ShareSockets, SharedMod},
+    lockfile::{
+        compare_mod_lists, create_lock_file, read_lock_file, verify_lock_file, write_lock_file,
+        ModDifference,
+    },
+    modpack::{
+        export_modpack, plan_modpack_import, read_modpack, write_modpack, Modpack, ModpackImport,
+    },
+    reconcile::{self, Plan},
+    steam_api::{
+        build_barony_mod, build_local_mods, build_offline_barony_mod, get_barony_workshop_mods,
+        get_published_file_details, get_workshop_collection, parse_workshop_id, parse_workshop_ids,
+        SteamWebApi,
+    },
     styling::{
         DownloadModButton, DownloadingModButton, GeneralUiStyles, ModCardUiStyles, RemoveModButton,
     },
-    widgets::{Filter, Message, PickableTag, Sorter, SortingStrategy},
+    versions::{self, StoredVersion},
+    watchlist::{watch_notifications, Notification, Watchlist},
+    widgets::{Filter, Message, PickableTag, RefreshInterval, Sorter, SortingStrategy},
 };
-use chrono::Datelike;
+use chrono::{DateTime, Datelike, Utc};
 use iced::{
-    button, executor, pick_list, scrollable, text_input, window, Align, Application, Button,
-    Clipboard, Color, Column, Command, Container, Element, Image, Length, PickList, Row,
-    Scrollable, Settings, Subscription, Text, TextInput,
+    button, executor, futures::channel::oneshot, pick_list, scrollable, text_input, window, Align,
+    Application, Button, Checkbox, Clipboard, Color, Column, Command, Container, Element, Image,
+    Length, PickList, Row, Scrollable, Settings, Subscription, Text, TextInput,
 };
 use iced_native::Event;
 use reqwest::Client;
 
 static VERSION: &str = "v0.3.3";
+/// Older notifications are dropped
+const MAX_NOTIFICATIONS: usize = 200;
+
+/// The app is built without a console on Windows, so the command line interface borrows the
+/// one of the terminal it was started from to be able to print anything.
+#[cfg(windows)]
+fn attach_console() {
+    use winapi::um::wincon::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+    // Nothing to attach to when not started from a terminal, which is fine
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
 
 fn main() -> iced::Result {
+    // Any argument means the command line interface is being used
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        attach_console();
+        if let Err(err) = cli::run(args) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let icon = build_app_logo().unwrap();
 
     BaronyModManager::run(iced::Settings {
@@ -38,11 +99,113 @@ fn main() -> iced::Result {
     })
 }
 
+struct RemovalPrompt {
+    id: String,
+    dependents: Vec<String>,
+}
+
+/// Another manager on the local network we can get mods from.
+struct Peer {
+    address: String,
+    mods: Vec<SharedMod>,
+}
+
+/// The folder shown by the archive chooser, with a button for each entry.
+struct ArchiveChooser {
+    dir: PathBuf,
+    entries: Vec<(ChooserEntry, button::State)>,
+    parent_button: button::State,
+    close_button: button::State,
+}
+
+impl ArchiveChooser {
+    fn open(dir: PathBuf) -> io::Result<ArchiveChooser> {
+        let entries = filesystem::list_chooser_entries(&dir)?
+            .into_iter()
+            .map(|entry| (entry, button::State::new()))
+            .collect();
+
+        Ok(ArchiveChooser {
+            dir,
+            entries,
+            parent_button: button::State::new(),
+            close_button: button::State::new(),
+        })
+    }
+}
+
+/// Where a downloaded mod goes, and the install it replaces.
+struct ExtractTarget {
+    installation: String,
+    barony_dir: String,
+    folder: String,
+    previous: Option<InstalledMod>,
+}
+
+impl ExtractTarget {
+    fn new(state: &BaronyModManager, id: &str) -> ExtractTarget {
+        let previous = state.installed.get(id).cloned();
+        let folder = match &previous {
+            Some(installed) => installed.folder.clone(),
+            None => state
+                .mods
+                .iter()
+                .flatten()
+                .find(|mod_| mod_.workshop.id == id)
+                .map(|mod_| mod_.workshop.title.clone())
+                .unwrap_or_default(),
+        };
+
+        ExtractTarget {
+            installation: state.active_installation.clone(),
+            barony_dir: state.barony_dir_str.clone(),
+            folder,
+            previous,
+        }
+    }
+
+    /// Writes the archive in the mods folder, keeping the version it replaces to be able to
+    /// roll back.
+    fn install(&self, id: &str, zip_bytes: Vec<u8>) -> io::Result<()> {
+        versions::install_version(
+            &self.installation,
+            &self.barony_dir,
+            id,
+            self.previous.as_ref(),
+            &self.folder,
+            zip_bytes,
+        )
+    }
+
+    fn extract(&self, id: &str, zip_bytes: Vec<u8>) -> Message {
+        match self.install(id, zip_bytes) {
+            Ok(_) => Message::ModDownloaded(id.to_string()),
+            Err(err) => Message::ModDownloadError(id.to_string(), err.to_string()),
+        }
+    }
+}
+
 /// App state
 struct BaronyModManager {
     // Core data
     mods: Option<Vec<BaronyMod>>,
     http_client: Client,
+    installed: HashMap<String, InstalledMod>,
+    // Folders in `mods/` that don't belong to any known mod
+    local_mods: Vec<LocalMod>,
+
+    // Mods waiting for their dependencies to be installed, along with the ids of the
+    // dependencies that are still missing
+    pending_installs: HashMap<String, HashSet<String>>,
+    // Downloaded mods with dependencies nothing else needs, computed again when the
+    // installs change
+    removable_with_dependencies: Option<HashSet<String>>,
+
+    // Shown when removing a mod that other downloaded mods depend on
+    removal_prompt: Option<RemovalPrompt>,
+    confirm_removal_button: button::State,
+    remove_with_dependencies_button: button::State,
+    cancel_removal_button: button::State,
 
     // Mod querying
     mod_search_input: text_input::State,
@@ -60,12 +223,94 @@ struct BaronyModManager {
     sorting_strategy: Option<SortingStrategy>,
 
     loading_mods: bool,
+    refresh_interval: RefreshInterval,
+    refresh_interval_picklist: pick_list::State<RefreshInterval>,
+
+    // Barony installations, and the name of the one in use
+    installations: Vec<Installation>,
+    active_installation: String,
+    installation_picklist: pick_list::State<String>,
+    installation_name_str: String,
+    installation_name_input: text_input::State,
+    add_installation_button: button::State,
+    remove_installation_button: button::State,
 
     // Barony dir input
     barony_dir_str: String,
     barony_dir_input: text_input::State,
     barony_dir_valid: bool,
 
+    // Install from archive input
+    archive_path_str: String,
+    archive_path_input: text_input::State,
+    install_archive_button: button::State,
+    choose_archive_button: button::State,
+    archive_chooser: Option<ArchiveChooser>,
+
+    // Modpack inputs
+    modpack_path_str: String,
+    modpack_path_input: text_input::State,
+    modpack_name_str: String,
+    modpack_name_input: text_input::State,
+    modpack_description_str: String,
+    modpack_description_input: text_input::State,
+    export_modpack_button: button::State,
+    import_modpack_button: button::State,
+
+    // Reconciliation of the mods folder against the modpack
+    prune_mods: bool,
+    plan: Option<Plan>,
+    plan_button: button::State,
+    apply_plan_button: button::State,
+
+    // Lock file of the installed mods
+    lock_path_str: String,
+    lock_path_input: text_input::State,
+    write_lock_button: button::State,
+    verify_lock_button: button::State,
+
+    // Fingerprint of the installed mods and comparison with another player's mods
+    fingerprint: Option<String>,
+    copy_fingerprint_button: button::State,
+    comparison: Option<Vec<ModDifference>>,
+    compare_button: button::State,
+    close_comparison_button: button::State,
+
+    // Sharing mods with other managers on the local network
+    share_stop: Option<oneshot::Sender<()>>,
+    share_port: Option<u16>,
+    share_button: button::State,
+    peer: Option<Peer>,
+    peer_address_str: String,
+    peer_address_input: text_input::State,
+    connect_peer_button: button::State,
+    find_peers_button: button::State,
+
+    // Offline bundles of installed mods
+    bundle_selection: HashSet<String>,
+    bundle_path_str: String,
+    bundle_path_input: text_input::State,
+    export_bundle_button: button::State,
+    import_bundle_button: button::State,
+
+    // Workshop collections kept in sync
+    collections: Vec<TrackedCollection>,
+    collection_input_str: String,
+    collection_input: text_input::State,
+    add_collection_button: button::State,
+
+    // Installing workshop items by url or id
+    pasted_ids_str: String,
+    pasted_ids_input: text_input::State,
+    install_pasted_button: button::State,
+
+    // Steam Web API settings, empty values use the defaults
+    steam_api_key_str: String,
+    steam_api_key_input: text_input::State,
+    steam_api_url_str: String,
+    steam_api_url_input: text_input::State,
+    refresh_installed_button: button::State,
+
     // Button
     search_button_state: button::State,
     filter_picklist: pick_list::State<Filter>,
@@ -73,9 +318,37 @@ struct BaronyModManager {
 
     mods_scrollable: scrollable::State,
 
+    // Catalogs fetched before, and what changed since the last launch
+    history: Vec<CatalogSnapshot>,
+    history_baseline: Option<CatalogSnapshot>,
+    whats_new: WhatsNew,
+    trends: HashMap<String, Trend>,
+
+    // Previous versions of the mods by workshop id, newest first
+    stored_versions: HashMap<String, Vec<StoredVersion>>,
+
+    // Downloaded archives kept for reinstalls
+    cache_stats: CacheStats,
+    purge_cache_button: button::State,
+
+    // Watched mods and tags, and what happened to them
+    watchlist: Watchlist,
+    watch_tag_button: button::State,
+    notifications: Vec<Notification>,
+    show_notifications: bool,
+    notifications_button: button::State,
+    clear_notifications_button: button::State,
+
+    // Catalog parsing report
+    catalog_diagnostics: CatalogDiagnostics,
+    show_catalog_report: bool,
+    catalog_report_button: button::State,
+
     // Misc
     should_exit: bool,
     error_message: Option<String>,
+    // Outcome of the last action that has no better place to be shown
+    status_message: Option<String>,
 }
 
 impl Application for BaronyModManager {
@@ -92,23 +365,51 @@ impl Application for BaronyModManager {
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        iced_native::subscription::events_with(|event, _other| match event {
-            // Listen only for window close requests, don't triggering unnecessary renders
+        let events = iced_native::subscription::events_with(|event, _other| match event {
+            // Listen only for window close requests and dropped files, don't triggering
+            // unnecessary renders
             Event::Window(iced_native::window::Event::CloseRequested) => {
                 Some(Message::CloseRequested)
             }
+            Event::Window(iced_native::window::Event::FileDropped(path)) => {
+                Some(Message::InstallArchive(path))
+            }
             _ => None,
-        })
+        });
+
+        match self.refresh_interval {
+            RefreshInterval(0) => events,
+            RefreshInterval(minutes) => Subscription::batch(vec![
+                events,
+                iced::time::every(Duration::from_secs(minutes * 60))
+                    .map(|_| Message::ScheduledRefresh),
+            ]),
+        }
     }
 
     fn new(_flags: Self::Flags) -> (BaronyModManager, Command<Message>) {
         let persisted_settings = filesystem::load_persisted_settings();
-        let barony_dir = persisted_settings.barony_directory_path.unwrap_or_default();
+        let installation = persisted_settings
+            .installation()
+            .cloned()
+            .unwrap_or_else(|| Installation::new(DEFAULT_INSTALLATION.to_string(), String::new()));
+        let barony_dir = installation.barony_directory_path.clone();
+        let history = filesystem::load_catalog_history();
 
         let initial_state = BaronyModManager {
             mods: None,
 
             http_client: Client::new(),
+            installed: filesystem::load_installed_mods(&installation.name),
+            local_mods: vec![],
+            pending_installs: HashMap::new(),
+            removable_with_dependencies: None,
+
+            removal_prompt: None,
+            confirm_removal_button: button::State::default(),
+            remove_with_dependencies_button: button::State::default(),
+            cancel_removal_button: button::State::default(),
+
             tags: HashSet::new(),
             // Mod querying
             mod_search_input: text_input::State::default(),
@@ -121,35 +422,137 @@ impl Application for BaronyModManager {
             sorting_strategy: Some(SortingStrategy::default()),
 
             loading_mods: false,
+            refresh_interval: installation
+                .refresh_interval_minutes
+                .map(RefreshInterval)
+                .unwrap_or_default(),
+            refresh_interval_picklist: pick_list::State::default(),
             tag_picklist: pick_list::State::default(),
             selected_tag: Some(PickableTag::default()),
 
+            installations: persisted_settings.installations,
+            active_installation: installation.name.clone(),
+            installation_picklist: pick_list::State::default(),
+            installation_name_str: String::new(),
+            installation_name_input: text_input::State::default(),
+            add_installation_button: button::State::default(),
+            remove_installation_button: button::State::default(),
+
             barony_dir_valid: barony_dir_valid(&barony_dir),
             barony_dir_str: barony_dir,
             barony_dir_input: text_input::State::default(),
 
+            archive_path_str: String::new(),
+            archive_path_input: text_input::State::default(),
+            install_archive_button: button::State::default(),
+            choose_archive_button: button::State::default(),
+            archive_chooser: None,
+
+            modpack_path_str: String::new(),
+            modpack_path_input: text_input::State::default(),
+            modpack_name_str: String::new(),
+            modpack_name_input: text_input::State::default(),
+            modpack_description_str: String::new(),
+            modpack_description_input: text_input::State::default(),
+            export_modpack_button: button::State::default(),
+            import_modpack_button: button::State::default(),
+
+            prune_mods: false,
+            plan: None,
+            plan_button: button::State::default(),
+            apply_plan_button: button::State::default(),
+
+            lock_path_str: String::new(),
+            lock_path_input: text_input::State::default(),
+            write_lock_button: button::State::default(),
+            verify_lock_button: button::State::default(),
+
+            fingerprint: None,
+            copy_fingerprint_button: button::State::default(),
+            comparison: None,
+            compare_button: button::State::default(),
+            close_comparison_button: button::State::default(),
+
+            share_stop: None,
+            share_port: None,
+            share_button: button::State::default(),
+            peer: None,
+            peer_address_str: String::new(),
+            peer_address_input: text_input::State::default(),
+            connect_peer_button: button::State::default(),
+            find_peers_button: button::State::default(),
+
+            bundle_selection: HashSet::new(),
+            bundle_path_str: String::new(),
+            bundle_path_input: text_input::State::default(),
+            export_bundle_button: button::State::default(),
+            import_bundle_button: button::State::default(),
+
+            collections: filesystem::load_collections(&installation.name)
+                .into_iter()
+                .map(TrackedCollection::new)
+                .collect(),
+            collection_input_str: String::new(),
+            collection_input: text_input::State::default(),
+            add_collection_button: button::State::default(),
+
+            pasted_ids_str: String::new(),
+            pasted_ids_input: text_input::State::default(),
+            install_pasted_button: button::State::default(),
+
+            steam_api_key_str: installation.steam_api_key.clone().unwrap_or_default(),
+            steam_api_key_input: text_input::State::default(),
+            steam_api_url_str: installation.steam_api_url.clone().unwrap_or_default(),
+            steam_api_url_input: text_input::State::default(),
+            refresh_installed_button: button::State::default(),
+
             search_button_state: button::State::default(),
 
             mods_scrollable: scrollable::State::default(),
 
+            history_baseline: history.last().cloned(),
+            history,
+            whats_new: WhatsNew::default(),
+            trends: HashMap::new(),
+
+            stored_versions: versions::list_stored_versions(&installation.name),
+
+            cache_stats: CacheStats::default(),
+            purge_cache_button: button::State::default(),
+
+            watchlist: filesystem::load_watchlist(&installation.name),
+            watch_tag_button: button::State::default(),
+            notifications: filesystem::load_notifications(&installation.name),
+            show_notifications: false,
+            notifications_button: button::State::default(),
+            clear_notifications_button: button::State::default(),
+
+            catalog_diagnostics: CatalogDiagnostics::default(),
+            show_catalog_report: false,
+            catalog_report_button: button::State::default(),
+
             filter_picklist: pick_list::State::default(),
             selected_filter: Some(Filter::default()),
 
             should_exit: false,
             error_message: None,
+            status_message: None,
         };
 
         let duration = Duration::from_millis(1);
         (
             initial_state,
-            Command::perform(async_std::task::sleep(duration), |_| Message::LoadMods),
+            Command::batch(vec![
+                Command::perform(async_std::task::sleep(duration), |_| Message::LoadMods),
+                read_cache_stats(),
+            ]),
         )
     }
 
     fn update(
         &mut self,
         message: Self::Message,
-        _clipboard: &mut Clipboard,
+        clipboard: &mut Clipboard,
     ) -> Command<Self::Message> {
         match message {
             Message::ModSearchInputChanged(new_value) => {
@@ -157,16 +560,43 @@ impl Application for BaronyModManager {
                 Command::none()
             }
             Message::LoadMods => {
-                self.mods = None;
                 self.loading_mods = true;
                 Command::perform(
                     get_barony_workshop_mods(self.http_client.clone()),
                     |result| match result {
-                        Ok(workshop_mods) => Message::ModsFetched(workshop_mods),
+                        Ok(catalog) => Message::ModsFetched(catalog),
                         Err(message) => Message::ErrorHappened(message.to_string()),
                     },
                 )
             }
+            Message::ScheduledRefresh => {
+                // A manual refresh is running, or the first load failed
+                if self.loading_mods || self.mods.is_none() {
+                    return Command::none();
+                }
+
+                self.loading_mods = true;
+                Command::perform(
+                    get_barony_workshop_mods(self.http_client.clone()),
+                    |result| match result {
+                        Ok(catalog) => Message::ModsFetched(catalog),
+                        Err(message) => Message::ScheduledRefreshFailed(message.to_string()),
+                    },
+                )
+            }
+            Message::ScheduledRefreshFailed(message) => {
+                // Not worth hiding the mods for, the next refresh may work
+                self.loading_mods = false;
+                self.status_message = Some(format!("Could not refresh the catalog: {}", message));
+                Command::none()
+            }
+            Message::RefreshIntervalSelected(interval) => {
+                self.refresh_interval = interval;
+                if let Some(installation) = active_installation_mut(self) {
+                    installation.refresh_interval_minutes = Some(interval.0);
+                }
+                Command::none()
+            }
             Message::SortingStrategySelected(new_strategy) => {
                 self.sorting_strategy = Some(new_strategy);
                 sort_mods(self);
@@ -186,196 +616,1595 @@ impl Application for BaronyModManager {
                 Command::none()
             }
             Message::BaronyDirectoryPathChanged(new_value) => {
+                if let Some(installation) = active_installation_mut(self) {
+                    installation.barony_directory_path = new_value.clone();
+                }
+
                 self.barony_dir_valid = barony_dir_valid(&new_value);
                 self.barony_dir_str = new_value;
+
+                if self.barony_dir_valid {
+                    scan_local_mods(self, vec![])
+                } else {
+                    self.local_mods.clear();
+                    self.fingerprint = None;
+                    Command::none()
+                }
+            }
+            Message::ArchivePathChanged(new_value) => {
+                self.archive_path_str = new_value;
                 Command::none()
             }
-            Message::CloseRequested => {
-                // Cleanup
-                filesystem::persist_settings(filesystem::SettingsPersistance {
-                    barony_directory_path: Some(self.barony_dir_str.clone()),
-                });
-                self.should_exit = true;
+            Message::InstallArchive(path) => {
+                if !self.barony_dir_valid {
+                    self.status_message =
+                        Some("Set a valid Barony directory before installing mods".to_string());
+                    return Command::none();
+                }
+
+                let barony_dir = self.barony_dir_str.clone();
+                self.status_message = Some(format!("Installing {}...", path.display()));
+                Command::perform(
+                    async move { filesystem::install_mod_archive(barony_dir, path) },
+                    |result| Message::ArchiveInstalled(result.map_err(|err| err.to_string())),
+                )
+            }
+            Message::OpenArchiveChooser => {
+                // Opens next to the archive typed in, if any
+                let typed = PathBuf::from(&self.archive_path_str);
+                let dir = typed
+                    .parent()
+                    .filter(|parent| parent.is_dir())
+                    .map(PathBuf::from)
+                    .unwrap_or_else(filesystem::default_chooser_dir);
+                open_archive_chooser(self, dir);
                 Command::none()
             }
-            Message::ModsFetched(steam_workshop_mods) => {
-                for mod_ in &steam_workshop_mods {
-                    for tag in &mod_.tags {
-                        let pickable = PickableTag::Some(tag.clone());
-                        self.tags.insert(pickable);
-                    }
+            Message::ArchiveChooserDir(dir) => {
+                open_archive_chooser(self, dir);
+                Command::none()
+            }
+            Message::ArchiveChosen(path) => {
+                self.archive_path_str = path.to_string_lossy().to_string();
+                self.archive_chooser = None;
+                Command::none()
+            }
+            Message::CloseArchiveChooser => {
+                self.archive_chooser = None;
+                Command::none()
+            }
+            Message::ArchiveInstalled(result) => match result {
+                Ok(folder) => {
+                    self.archive_path_str.clear();
+                    self.status_message = Some(format!(
+                        "\"{}\" was installed and is listed under the local mods",
+                        folder
+                    ));
+                    scan_local_mods(self, vec![])
                 }
+                Err(err) => {
+                    self.status_message = Some(format!("Could not install the archive: {}", err));
+                    Command::none()
+                }
+            },
+            Message::ModpackPathChanged(new_value) => {
+                self.modpack_path_str = new_value;
+                Command::none()
+            }
+            Message::ModpackNameChanged(new_value) => {
+                self.modpack_name_str = new_value;
+                Command::none()
+            }
+            Message::ModpackDescriptionChanged(new_value) => {
+                self.modpack_description_str = new_value;
+                Command::none()
+            }
+            Message::ExportModpack => {
+                let name =
+                    Some(self.modpack_name_str.trim().to_string()).filter(|name| !name.is_empty());
+                let description = Some(self.modpack_description_str.trim().to_string())
+                    .filter(|description| !description.is_empty());
+                let modpack = export_modpack(name, description, self.installed.iter());
+                let path = PathBuf::from(&self.modpack_path_str);
 
-                Command::batch(steam_workshop_mods.into_iter().map(|mod_| {
-                    Command::perform(
-                        build_barony_mod(
-                            self.http_client.clone(),
-                            self.barony_dir_str.clone(),
-                            mod_,
-                        ),
-                        |m| Message::ModBuilt(Box::new(m)),
-                    )
-                }))
+                self.status_message = match write_modpack(&path, &modpack) {
+                    Ok(_) => Some(format!(
+                        "Exported {} mods to {}",
+                        modpack.mods.len(),
+                        path.display()
+                    )),
+                    Err(err) => Some(format!("Could not export the modpack: {}", err)),
+                };
+                Command::none()
             }
-            Message::ModBuilt(barony_mod) => {
-                self.loading_mods = false;
-                self.error_message = None;
+            Message::ImportModpack => {
+                let modpack = match read_modpack(&PathBuf::from(&self.modpack_path_str)) {
+                    Ok(modpack) => modpack,
+                    Err(err) => {
+                        self.status_message = Some(format!("Could not read the modpack: {}", err));
+                        return Command::none();
+                    }
+                };
 
-                if let Some(mods) = &mut self.mods {
-                    mods.push(*barony_mod) // self.mods.unwrap().push(barony_mod)
-                } else {
-                    self.mods = Some(vec![*barony_mod])
-                }
+                let import = match &self.mods {
+                    Some(mods) => plan_modpack_import(&modpack, mods, &self.installed),
+                    None => {
+                        self.status_message =
+                            Some("Load the mods before importing a modpack".to_string());
+                        return Command::none();
+                    }
+                };
+
+                self.status_message = Some(describe_modpack_import(self, &modpack, &import));
 
+                let mut commands = vec![];
+                for id in import.to_install {
+                    commands.push(install_mod(self, id));
+                }
+                Command::batch(commands)
+            }
+            Message::PruneModsToggled(prune) => {
+                self.prune_mods = prune;
+                self.plan = None;
                 Command::none()
             }
-            Message::DownloadMod(id) => {
-                // TODO: Make function for this
-                let selected_mod = self
+            Message::PlanModpack => {
+                let manifest = match read_modpack(&PathBuf::from(&self.modpack_path_str)) {
+                    Ok(manifest) => manifest,
+                    Err(err) => {
+                        self.status_message = Some(format!("Could not read the modpack: {}", err));
+                        return Command::none();
+                    }
+                };
+
+                let catalog = self
                     .mods
-                    .as_mut()
-                    .unwrap()
-                    .iter_mut()
-                    .find(|_mod| _mod.workshop.id == id)
-                    .unwrap();
+                    .iter()
+                    .flatten()
+                    .filter(|mod_| !mod_.delisted)
+                    .map(|mod_| mod_.workshop.clone())
+                    .collect::<Vec<_>>();
+                let barony_dir = &self.barony_dir_str;
+                let plan = reconcile::plan(
+                    &manifest,
+                    &catalog,
+                    &self.installed,
+                    |folder| filesystem::is_mod_folder_present(barony_dir, folder),
+                    self.prune_mods,
+                );
 
-                selected_mod.download_status = DownloadStatus::Preparing;
-                Command::perform(
-                    queue_download(self.http_client.clone(), id.parse::<u32>().unwrap()),
-                    move |result| match result {
-                        Ok(uuid) => Message::PreparingModDownload(id.clone(), uuid),
-                        Err(err) => Message::ModDownloadError(id.clone(), err.to_string()),
-                    },
-                )
+                self.status_message = Some(plan.to_string());
+                self.plan = Some(plan).filter(Plan::has_changes);
+                Command::none()
             }
-            Message::PreparingModDownload(id, uuid) => Command::perform(
-                check_status(self.http_client.clone(), uuid.clone()),
-                move |result| match result {
-                    Ok(true) => Message::ModDownloadReady(id.clone(), uuid.clone()),
-                    Ok(false) => Message::PreparingModDownload(id.clone(), uuid.clone()),
-                    Err(err) => Message::ModDownloadError(id.clone(), err.to_string()),
-                },
-            ),
-            Message::ModDownloadReady(id, uuid) => {
-                let selected_mod = self
-                    .mods
-                    .as_mut()
-                    .unwrap()
-                    .iter_mut()
-                    .find(|_mod| _mod.workshop.id == id)
-                    .unwrap();
+            Message::ApplyPlan => {
+                let plan = match self.plan.take() {
+                    Some(plan) => plan,
+                    None => return Command::none(),
+                };
 
-                selected_mod.download_status = DownloadStatus::Downloading;
+                self.status_message = Some(format!(
+                    "Applying plan: {} to install, {} to update, {} to remove.",
+                    plan.install.len(),
+                    plan.update.len(),
+                    plan.remove.len()
+                ));
 
-                // TODO: Maybe try to use less clones here, but since it's a function
-                // that will run asynchronously, I'm not sure if it's possible without
-                // huge pain
+                let mut commands = vec![remove_mods(
+                    self,
+                    plan.remove.into_iter().map(|item| item.id).collect(),
+                )];
+                for item in plan.install {
+                    commands.push(install_mod(self, item.id));
+                }
+                for item in plan.update {
+                    commands.push(start_download(self, item.id));
+                }
+                Command::batch(commands)
+            }
+            Message::LockPathChanged(new_value) => {
+                self.lock_path_str = new_value;
+                Command::none()
+            }
+            Message::WriteLockFile => {
+                // Hashing every mod folder takes a while, so it's done in the background
+                let path = PathBuf::from(&self.lock_path_str);
                 let barony_dir = self.barony_dir_str.clone();
-                let mod_title = selected_mod.workshop.title.clone();
-
+                let installed = self.installed.clone();
                 Command::perform(
-                    download_mod(self.http_client.clone(), uuid),
-                    move |result| match result {
-                        Ok(zip_bytes) => {
-                            match filesystem::write_mod_to_disk(
-                                barony_dir.clone(),
-                                mod_title.clone(),
-                                zip_bytes,
-                            ) {
-                                Ok(_) => Message::ModDownloaded(id.clone()),
-                                Err(err) => Message::ModDownloadError(id.clone(), err.to_string()),
-                            }
-                        }
-                        Err(err) => Message::ModDownloadError(id.clone(), err.to_string()),
+                    async move {
+                        let result = create_lock_file(&barony_dir, &installed)
+                            .and_then(|lock| write_lock_file(&path, &lock).map(|_| lock))
+                            .map(|lock| lock.mods.len())
+                            .map_err(|err| err.to_string());
+                        (path, result)
                     },
+                    |(path, result)| Message::LockFileWritten(path, result),
                 )
             }
-            Message::ModDownloadError(id, msg) => {
-                let selected_mod = self
-                    .mods
-                    .as_mut()
-                    .unwrap()
-                    .iter_mut()
-                    .find(|_mod| _mod.workshop.id == id)
-                    .unwrap();
-
-                dbg!(&id, &msg);
-                selected_mod.download_status = DownloadStatus::ErrorOccurred(msg);
+            Message::LockFileWritten(path, result) => {
+                self.status_message = match result {
+                    Ok(count) => Some(format!("Locked {} mods to {}", count, path.display())),
+                    Err(err) => Some(format!("Could not write the lock file: {}", err)),
+                };
                 Command::none()
             }
-            Message::ModDownloaded(id) => {
-                let selected_mod = self
-                    .mods
-                    .as_mut()
-                    .unwrap()
-                    .iter_mut()
-                    .find(|_mod| _mod.workshop.id == id)
-                    .unwrap();
-
-                selected_mod.download_status = DownloadStatus::Downloaded;
+            Message::VerifyLockFile => {
+                let path = PathBuf::from(&self.lock_path_str);
+                let barony_dir = self.barony_dir_str.clone();
+                let installed = self.installed.clone();
+                Command::perform(
+                    async move {
+                        read_lock_file(&path)
+                            .and_then(|lock| {
+                                verify_lock_file(&lock, &barony_dir, &installed)
+                                    .map(|drifts| (lock.mods.len(), drifts))
+                            })
+                            .map_err(|err| err.to_string())
+                    },
+                    Message::LockFileVerified,
+                )
+            }
+            Message::LockFileVerified(result) => {
+                self.status_message = match result {
+                    Ok((count, drifts)) if drifts.is_empty() => {
+                        Some(format!("All {} mods match the lock file.", count))
+                    }
+                    Ok((_, drifts)) => Some(format!(
+                        "{} mods differ from the lock file:\n{}",
+                        drifts.len(),
+                        drifts
+                            .iter()
+                            .map(|drift| drift.to_string())
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    )),
+                    Err(err) => Some(format!("Could not verify the lock file: {}", err)),
+                };
                 Command::none()
             }
-            Message::RemoveMod(id) => {
-                let selected_mod = self
-                    .mods
-                    .as_mut()
-                    .unwrap()
-                    .iter_mut()
-                    .find(|_mod| _mod.workshop.id == id)
-                    .unwrap();
-
-                // TODO: treat error
-                match filesystem::delete_mod_from_disk(
-                    &self.barony_dir_str,
-                    &selected_mod.workshop.title,
-                ) {
-                    Ok(_) => selected_mod.download_status = DownloadStatus::NotDownloaded,
+            Message::CompareModList => {
+                let path = PathBuf::from(&self.lock_path_str);
+                let barony_dir = self.barony_dir_str.clone();
+                let installed = self.installed.clone();
+                Command::perform(
+                    async move {
+                        read_lock_file(&path)
+                            .and_then(|theirs| {
+                                create_lock_file(&barony_dir, &installed)
+                                    .map(|mine| compare_mod_lists(&mine, &theirs))
+                            })
+                            .map_err(|err| err.to_string())
+                    },
+                    Message::ModListCompared,
+                )
+            }
+            Message::ModListCompared(result) => {
+                match result {
+                    Ok(differences) if differences.is_empty() => {
+                        self.status_message =
+                            Some("Both players have exactly the same mods.".to_string());
+                        self.comparison = None;
+                    }
+                    Ok(differences) => {
+                        self.status_message = None;
+                        self.comparison = Some(differences);
+                    }
                     Err(err) => {
-                        selected_mod.download_status =
-                            DownloadStatus::ErrorOccurred(err.to_string())
+                        self.status_message =
+                            Some(format!("Could not compare the mod lists: {}", err))
                     }
                 }
-
                 Command::none()
             }
-            Message::ErrorHappened(msg) => {
-                self.error_message = Some(format!("An error occurred: {}", msg));
+            Message::CloseComparison => {
+                self.comparison = None;
                 Command::none()
             }
-        }
-    }
-
-    // TODO: Clean this view
-    fn view(&mut self) -> Element<Self::Message> {
-        // ------------------ Header -----------------------
-        let app_name = Text::new(format!("Barony Mod Manager {}", VERSION))
-            .size(30)
-            .width(Length::FillPortion(3))
-            .color(iced::Color::WHITE);
+            Message::FingerprintComputed(fingerprint) => {
+                self.fingerprint = fingerprint;
+                Command::none()
+            }
+            Message::CopyFingerprint => {
+                if let Some(fingerprint) = &self.fingerprint {
+                    clipboard.write(fingerprint.clone());
+                }
+                Command::none()
+            }
+            Message::ToggleSharing => {
+                // Dropping the sender stops the server
+                if self.share_stop.take().is_some() {
+                    self.share_port = None;
+                    self.status_message = Some("Stopped sharing the installed mods".to_string());
+                    return Command::none();
+                }
 
-        let mod_search_input = TextInput::new(
-            &mut self.mod_search_input,
-            "Search for mods...",
-            &self.query,
-            Message::ModSearchInputChanged,
-        )
-        .padding(5)
-        .width(Length::FillPortion(2))
-        .style(GeneralUiStyles)
-        .size(20);
+                if !self.barony_dir_valid {
+                    self.status_message =
+                        Some("Set a valid Barony directory before sharing mods".to_string());
+                    return Command::none();
+                }
 
-        let header = Row::new().push(app_name).push(mod_search_input);
+                let sockets = match ShareSockets::bind() {
+                    Ok(sockets) => sockets,
+                    Err(err) => {
+                        self.status_message = Some(format!("Could not share the mods: {}", err));
+                        return Command::none();
+                    }
+                };
 
-        // ------------------ Bottom inputs -----------------------
+                let port = sockets.port();
+                let (stop, stopped) = oneshot::channel();
+                self.share_stop = Some(stop);
+                self.share_port = Some(port);
+                self.status_message = Some(format!(
+                    "Sharing the installed mods on port {}. Other players can connect to this \
+                     computer's address, or find it with \"Find peers\".",
+                    port
+                ));
 
-        let path_label_message = format!(
-            "Barony directory {}",
-            if self.barony_dir_valid {
-                "(VALID)"
-            } else {
-                "(INVALID)"
+                Command::perform(
+                    lan_share::serve(
+                        sockets,
+                        self.barony_dir_str.clone(),
+                        self.installed.clone(),
+                        stopped,
+                    ),
+                    move |result| Message::SharingStopped(port, result),
+                )
             }
-        );
+            Message::SharingStopped(port, result) => {
+                // A new share may have been started already
+                if self.share_port == Some(port) {
+                    self.share_stop = None;
+                    self.share_port = None;
+                }
+                if let Err(err) = result {
+                    self.status_message = Some(format!("Sharing stopped: {}", err));
+                }
+                Command::none()
+            }
+            Message::PeerAddressChanged(new_value) => {
+                self.peer_address_str = new_value;
+                Command::none()
+            }
+            Message::FindPeers => {
+                self.status_message = Some("Looking for peers...".to_string());
+                Command::perform(lan_share::discover_peers(), |result| {
+                    Message::PeersFound(result.map_err(|err| err.to_string()))
+                })
+            }
+            Message::PeersFound(result) => {
+                let own_port = self.share_port;
+                match result {
+                    Ok(peers) => {
+                        let peers = peers
+                            .into_iter()
+                            .filter(|peer| Some(peer.port()) != own_port)
+                            .map(|peer| peer.to_string())
+                            .collect::<Vec<_>>();
+
+                        if let Some(first) = peers.first() {
+                            self.peer_address_str = first.clone();
+                            self.status_message =
+                                Some(format!("Found peers: {}", peers.join(", ")));
+                        } else {
+                            self.status_message = Some("No peers found".to_string());
+                        }
+                    }
+                    Err(err) => {
+                        self.status_message = Some(format!("Could not look for peers: {}", err))
+                    }
+                }
+                Command::none()
+            }
+            Message::ConnectPeer => {
+                let address = self.peer_address_str.trim().to_string();
+                if address.is_empty() {
+                    self.peer = None;
+                    return Command::none();
+                }
+
+                self.status_message = Some(format!("Connecting to {}...", address));
+                Command::perform(
+                    lan_share::fetch_peer_mods(self.http_client.clone(), address.clone()),
+                    move |result| {
+                        Message::PeerModsFetched(
+                            address.clone(),
+                            result.map_err(|err| err.to_string()),
+                        )
+                    },
+                )
+            }
+            Message::PeerModsFetched(address, result) => {
+                match result {
+                    Ok(mods) => {
+                        self.status_message = Some(format!(
+                            "Connected to {}, which shares {} mods. Use \"Get from peer\" on a mod to download it from there.",
+                            address,
+                            mods.len()
+                        ));
+                        self.peer = Some(Peer { address, mods });
+                    }
+                    Err(err) => {
+                        self.peer = None;
+                        self.status_message =
+                            Some(format!("Could not connect to {}: {}", address, err));
+                    }
+                }
+                Command::none()
+            }
+            Message::DownloadFromPeer(id) => {
+                let (address, version) = match &self.peer {
+                    Some(peer) => match peer.mods.iter().find(|shared| shared.id == id) {
+                        Some(shared) => (peer.address.clone(), shared.time_updated),
+                        None => return Command::none(),
+                    },
+                    None => return Command::none(),
+                };
+
+                match find_mod(self, &id) {
+                    Some(selected_mod) => {
+                        selected_mod.download_status = DownloadStatus::Downloading
+                    }
+                    None => return Command::none(),
+                }
+                let target = ExtractTarget::new(self, &id);
+
+                Command::perform(
+                    lan_share::download_from_peer(self.http_client.clone(), address, id.clone()),
+                    move |result| match result {
+                        Ok(zip_bytes) => match target.install(&id, zip_bytes) {
+                            Ok(_) => Message::PeerModDownloaded(id.clone(), version),
+                            Err(err) => Message::ModDownloadError(id.clone(), err.to_string()),
+                        },
+                        Err(err) => Message::ModDownloadError(id.clone(), err.to_string()),
+                    },
+                )
+            }
+            Message::PeerModDownloaded(id, version) => {
+                if let Some(selected_mod) = find_mod(self, &id) {
+                    selected_mod.download_status = DownloadStatus::Downloaded;
+                    let workshop = selected_mod.workshop.clone();
+                    record_installed(self, &workshop);
+                }
+
+                // The peer may not have the version in the catalog
+                if let Some(installed) = self.installed.get_mut(&id) {
+                    installed.installed_version = Some(version);
+                }
+                save_installed_mods(self);
+                self.stored_versions = versions::list_stored_versions(&self.active_installation);
+                refresh_fingerprint(self)
+            }
+            Message::BundleSelectionToggled(id, selected) => {
+                if selected {
+                    self.bundle_selection.insert(id);
+                } else {
+                    self.bundle_selection.remove(&id);
+                }
+                Command::none()
+            }
+            Message::BundlePathChanged(new_value) => {
+                self.bundle_path_str = new_value;
+                Command::none()
+            }
+            Message::ExportBundle => {
+                let barony_dir = self.barony_dir_str.clone();
+                let mods = self
+                    .bundle_selection
+                    .iter()
+                    .filter_map(|id| self.installed.get(id))
+                    .filter(|installed| {
+                        filesystem::is_mod_folder_present(&barony_dir, &installed.folder)
+                    })
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                if mods.is_empty() {
+                    self.status_message = Some(
+                        "Select the installed mods to bundle with \"Add to bundle\" first"
+                            .to_string(),
+                    );
+                    return Command::none();
+                }
+
+                self.status_message = Some(format!("Bundling {} mods...", mods.len()));
+                Command::perform(
+                    export_bundle(
+                        self.http_client.clone(),
+                        PathBuf::from(&self.bundle_path_str),
+                        barony_dir,
+                        mods,
+                    ),
+                    Message::BundleExported,
+                )
+            }
+            Message::BundleExported(result) => {
+                self.status_message = match result {
+                    Ok(count) => {
+                        self.bundle_selection.clear();
+                        Some(format!(
+                            "Bundled {} mods to {}",
+                            count, self.bundle_path_str
+                        ))
+                    }
+                    Err(err) => Some(format!("Could not write the bundle: {}", err)),
+                };
+                Command::none()
+            }
+            Message::ImportBundle => {
+                if !self.barony_dir_valid {
+                    self.status_message =
+                        Some("Set a valid Barony directory before importing a bundle".to_string());
+                    return Command::none();
+                }
+
+                self.status_message = Some("Importing the bundle...".to_string());
+                let path = PathBuf::from(&self.bundle_path_str);
+                let installation = self.active_installation.clone();
+                let barony_dir = self.barony_dir_str.clone();
+                let installed = self.installed.clone();
+                Command::perform(
+                    async move {
+                        import_bundle(&path, &installation, &barony_dir, &installed)
+                            .map_err(|err| err.to_string())
+                    },
+                    Message::BundleImported,
+                )
+            }
+            Message::BundleImported(result) => {
+                let imported = match result {
+                    Ok(imported) => imported,
+                    Err(err) => {
+                        self.status_message = Some(format!("Could not import the bundle: {}", err));
+                        return Command::none();
+                    }
+                };
+
+                // Works without the catalog, the bundle has everything the cards need
+                let catalog_loaded = self.mods.is_some();
+                let mods = self.mods.get_or_insert_with(Vec::new);
+                for bundled in &imported {
+                    let id = &bundled.installed.workshop.id;
+                    match mods.iter_mut().find(|mod_| &mod_.workshop.id == id) {
+                        Some(mod_) => mod_.download_status = DownloadStatus::Downloaded,
+                        None => {
+                            let mut mod_ = build_offline_barony_mod(
+                                &self.barony_dir_str,
+                                bundled.installed.workshop.clone(),
+                                bundled.preview.as_deref(),
+                            );
+                            mod_.delisted = catalog_loaded;
+                            mod_.download_status = DownloadStatus::Downloaded;
+                            mods.push(mod_);
+                        }
+                    }
+                }
+
+                for bundled in &imported {
+                    self.installed.insert(
+                        bundled.installed.workshop.id.clone(),
+                        bundled.installed.clone(),
+                    );
+                }
+
+                self.error_message = None;
+                self.loading_mods = false;
+                self.status_message =
+                    Some(format!("Imported {} mods from the bundle", imported.len()));
+                self.stored_versions = versions::list_stored_versions(&self.active_installation);
+                save_installed_mods(self);
+                sort_mods(self);
+                refresh_fingerprint(self)
+            }
+            Message::CollectionInputChanged(new_value) => {
+                self.collection_input_str = new_value;
+                Command::none()
+            }
+            Message::AddCollection => match parse_workshop_id(&self.collection_input_str) {
+                Some(id) => {
+                    self.collection_input_str.clear();
+                    self.update(Message::SyncCollection(id), clipboard)
+                }
+                None => {
+                    self.status_message = Some(format!(
+                        "\"{}\" is not a collection id or url",
+                        self.collection_input_str
+                    ));
+                    Command::none()
+                }
+            },
+            Message::SyncCollection(id) => {
+                if self.mods.is_none() {
+                    self.status_message =
+                        Some("Load the mods before syncing a collection".to_string());
+                    return Command::none();
+                }
+
+                self.status_message = Some(format!("Fetching the collection {}...", id));
+                Command::perform(
+                    get_workshop_collection(self.http_client.clone(), steam_web_api(self), id),
+                    |result| Message::CollectionFetched(result.map_err(|err| err.to_string())),
+                )
+            }
+            Message::CollectionFetched(Err(err)) => {
+                self.status_message = Some(format!("Could not fetch the collection: {}", err));
+                Command::none()
+            }
+            Message::CollectionFetched(Ok(mut fetched)) => {
+                let tracked = self
+                    .collections
+                    .iter()
+                    .find(|tracked| tracked.collection.id == fetched.id)
+                    .map(|tracked| &tracked.collection);
+                let changes = collection_changes(tracked, &fetched);
+                let previously_installed =
+                    tracked.map_or(vec![], |tracked| tracked.installed.clone());
+
+                let all_collections = self
+                    .collections
+                    .iter()
+                    .map(|tracked| tracked.collection.clone())
+                    .collect::<Vec<_>>();
+                // Delisted mods the user chose to keep stay, even out of the collection
+                let to_remove = items_to_remove(&fetched.id, &changes.removed, &all_collections)
+                    .into_iter()
+                    .filter(|id| {
+                        self.installed
+                            .get(id)
+                            .map_or(false, |installed| !installed.keep_delisted)
+                    })
+                    .collect::<Vec<_>>();
+
+                // Mods other installed mods still require go through the same prompt as
+                // removing them by hand
+                let removing = to_remove.clone();
+                let (to_remove, required): (Vec<_>, Vec<_>) =
+                    to_remove.into_iter().partition(|id| {
+                        self.mods.as_ref().map_or(true, |mods| {
+                            downloaded_dependents(id, mods)
+                                .iter()
+                                .all(|dependent| removing.contains(dependent))
+                        })
+                    });
+
+                let mut to_install = vec![];
+                let mut unknown = vec![];
+                for id in &changes.added {
+                    match find_mod(self, id) {
+                        Some(mod_) if !mod_.delisted => {
+                            if matches!(
+                                mod_.download_status,
+                                DownloadStatus::NotDownloaded | DownloadStatus::ErrorOccurred(_)
+                            ) {
+                                to_install.push(id.clone());
+                            }
+                        }
+                        Some(mod_) if mod_.download_status == DownloadStatus::Downloaded => {}
+                        _ => unknown.push(id.clone()),
+                    }
+                }
+
+                let mut status = format!(
+                    "{}: {} added, {} removed, {} not in the catalog.",
+                    fetched.title,
+                    changes.added.len(),
+                    changes.removed.len(),
+                    unknown.len()
+                );
+                if required.len() > 1 {
+                    status.push_str(&format!(
+                        " {} mods left the collection but other mods require them.",
+                        required.len()
+                    ));
+                }
+                self.status_message = Some(status);
+                if let (Some(id), Some(mods)) = (required.first(), &self.mods) {
+                    self.removal_prompt = Some(RemovalPrompt {
+                        id: id.clone(),
+                        dependents: downloaded_dependents(id, mods),
+                    });
+                }
+
+                // Only what the collection installs itself is removed with it later
+                fetched.installed = previously_installed
+                    .into_iter()
+                    .filter(|id| fetched.items.contains(id))
+                    .chain(to_install.iter().cloned())
+                    .chain(unknown.iter().cloned())
+                    .collect();
+
+                match self
+                    .collections
+                    .iter_mut()
+                    .find(|tracked| tracked.collection.id == fetched.id)
+                {
+                    Some(tracked) => tracked.collection = fetched,
+                    None => self.collections.push(TrackedCollection::new(fetched)),
+                }
+                save_collections(self);
+
+                let mut commands = vec![remove_mods(self, to_remove)];
+                for id in to_install {
+                    commands.push(install_mod(self, id));
+                }
+                if !unknown.is_empty() {
+                    commands.push(fetch_unknown_items(self, unknown));
+                }
+                Command::batch(commands)
+            }
+            Message::ForgetCollection(id) => {
+                self.collections
+                    .retain(|tracked| tracked.collection.id != id);
+                save_collections(self);
+                Command::none()
+            }
+            Message::PastedIdsChanged(new_value) => {
+                self.pasted_ids_str = new_value;
+                Command::none()
+            }
+            Message::InstallPastedIds => {
+                if !self.barony_dir_valid {
+                    self.status_message =
+                        Some("Set a valid Barony directory before installing mods".to_string());
+                    return Command::none();
+                }
+
+                let (ids, invalid) = parse_workshop_ids(&self.pasted_ids_str);
+                if !invalid.is_empty() {
+                    self.status_message =
+                        Some(format!("Not workshop urls or ids: {}", invalid.join(", ")));
+                    return Command::none();
+                }
+                self.pasted_ids_str.clear();
+
+                let mut commands = vec![];
+                let mut unknown = vec![];
+                for id in ids {
+                    match find_mod(self, &id) {
+                        Some(mod_) if !mod_.delisted => {
+                            if matches!(
+                                mod_.download_status,
+                                DownloadStatus::NotDownloaded | DownloadStatus::ErrorOccurred(_)
+                            ) {
+                                commands.push(install_mod(self, id));
+                            }
+                        }
+                        _ => unknown.push(id),
+                    }
+                }
+
+                if !unknown.is_empty() {
+                    commands.push(fetch_unknown_items(self, unknown));
+                }
+
+                Command::batch(commands)
+            }
+            Message::PastedDetailsFetched(ids, result) => {
+                let mut commands = vec![];
+                let details = match result {
+                    Ok(catalog) => catalog.mods,
+                    Err(err) => {
+                        // Without the details the dependencies are unknown, so nothing is
+                        // downloaded and the items can be retried from their card
+                        self.status_message =
+                            Some(format!("Could not get the details of the items: {}", err));
+                        for id in &ids {
+                            if let Some(mod_) = find_mod(self, id) {
+                                mod_.download_status = DownloadStatus::ErrorOccurred(format!(
+                                    "could not get the details: {}",
+                                    err
+                                ));
+                            }
+                        }
+                        return Command::none();
+                    }
+                };
+
+                for id in ids {
+                    match details.iter().find(|workshop| workshop.id == id).cloned() {
+                        // Steam doesn't know the item, or it's not a Barony mod
+                        None => {
+                            if let Some(mod_) = find_mod(self, &id) {
+                                mod_.download_status = DownloadStatus::ErrorOccurred(
+                                    "not a Barony workshop item".to_string(),
+                                );
+                            }
+                        }
+                        Some(workshop) => {
+                            if let Some(mod_) = find_mod(self, &id) {
+                                mod_.workshop = workshop.clone();
+                            }
+                            commands.push(Command::perform(
+                                build_barony_mod(
+                                    self.http_client.clone(),
+                                    self.barony_dir_str.clone(),
+                                    workshop,
+                                ),
+                                |m| Message::ModBuilt(Box::new(m)),
+                            ));
+                            commands.push(install_mod(self, id));
+                        }
+                    }
+                }
+
+                sort_mods(self);
+                Command::batch(commands)
+            }
+            Message::SteamApiKeyChanged(new_value) => {
+                if let Some(installation) = active_installation_mut(self) {
+                    installation.steam_api_key = Some(new_value.clone());
+                }
+                self.steam_api_key_str = new_value;
+                Command::none()
+            }
+            Message::SteamApiUrlChanged(new_value) => {
+                if let Some(installation) = active_installation_mut(self) {
+                    installation.steam_api_url = Some(new_value.clone());
+                }
+                self.steam_api_url_str = new_value;
+                Command::none()
+            }
+            Message::RefreshModDetails(id) => refresh_details(self, vec![id]),
+            Message::RefreshInstalledDetails => {
+                let mut ids = self.installed.keys().cloned().collect::<Vec<_>>();
+                ids.sort();
+                if ids.is_empty() {
+                    self.status_message =
+                        Some("There are no installed mods to refresh".to_string());
+                    return Command::none();
+                }
+
+                refresh_details(self, ids)
+            }
+            Message::ModDetailsRefreshed(Err(err)) => {
+                self.status_message = Some(format!("Could not refresh the details: {}", err));
+                Command::none()
+            }
+            Message::ModDetailsRefreshed(Ok(catalog)) => {
+                let mut commands = vec![];
+                let refreshed = catalog.mods.len();
+
+                for mut workshop in catalog.mods {
+                    let previous = find_mod(self, &workshop.id)
+                        .map(|mod_| mod_.workshop.clone())
+                        .or_else(|| {
+                            self.installed
+                                .get(&workshop.id)
+                                .map(|installed| installed.workshop.clone())
+                        });
+                    if let Some(previous) = previous {
+                        workshop.keep_missing_details(&previous);
+                    }
+
+                    if let Some(installed) = self.installed.get_mut(&workshop.id) {
+                        // The version on disk doesn't change with the metadata
+                        installed.installed_version = Some(installed.version());
+                        installed.workshop = workshop.clone();
+                    }
+
+                    let preview_changed = match find_mod(self, &workshop.id) {
+                        Some(mod_) => {
+                            let changed = mod_.workshop.preview_url != workshop.preview_url
+                                || mod_.workshop.previews != workshop.previews;
+                            mod_.workshop = workshop.clone();
+                            changed
+                        }
+                        None => false,
+                    };
+
+                    if preview_changed {
+                        commands.push(Command::perform(
+                            build_barony_mod(
+                                self.http_client.clone(),
+                                self.barony_dir_str.clone(),
+                                workshop,
+                            ),
+                            |m| Message::ModBuilt(Box::new(m)),
+                        ));
+                    }
+                }
+
+                save_installed_mods(self);
+                sort_mods(self);
+                self.status_message = Some(format!("Refreshed the details of {} mods", refreshed));
+                commands.push(auto_update(self));
+                Command::batch(commands)
+            }
+            Message::LocalModsScanned(local_mods) => {
+                self.local_mods = local_mods;
+                refresh_fingerprint(self)
+            }
+            Message::LocalModLinkInputChanged(folder, value) => {
+                if let Some(local) = self.local_mods.iter_mut().find(|l| l.folder == folder) {
+                    local.link_value = value;
+                }
+                Command::none()
+            }
+            Message::LinkLocalMod(folder) => {
+                let input = self
+                    .local_mods
+                    .iter()
+                    .find(|local| local.folder == folder)
+                    .map(|local| local.link_value.clone())
+                    .unwrap_or_default();
+
+                let id = match parse_workshop_id(&input) {
+                    Some(id) => id,
+                    None => {
+                        self.status_message =
+                            Some(format!("\"{}\" is not a workshop id or url", input));
+                        return Command::none();
+                    }
+                };
+
+                self.local_mods.retain(|local| local.folder != folder);
+                let known_mod = find_mod(self, &id).map(|mod_| {
+                    mod_.download_status = DownloadStatus::Downloaded;
+                    mod_.workshop.clone()
+                });
+                let workshop = known_mod
+                    .clone()
+                    .unwrap_or_else(|| SteamWorkshopMod::placeholder(&id));
+
+                self.status_message = Some(format!(
+                    "The folder \"{}\" is now tracked as \"{}\"",
+                    folder, workshop.title
+                ));
+                self.installed
+                    .insert(id, InstalledMod::new(folder, workshop.clone()));
+                save_installed_mods(self);
+
+                if known_mod.is_some() {
+                    refresh_fingerprint(self)
+                } else {
+                    // Not in the catalog, shown with the little we know about it
+                    let build_command = Command::perform(
+                        build_barony_mod(
+                            self.http_client.clone(),
+                            self.barony_dir_str.clone(),
+                            workshop,
+                        ),
+                        |mut m| {
+                            m.delisted = true;
+                            m.download_status = DownloadStatus::Downloaded;
+                            Message::ModBuilt(Box::new(m))
+                        },
+                    );
+                    Command::batch(vec![build_command, refresh_fingerprint(self)])
+                }
+            }
+            Message::CloseRequested => {
+                // Cleanup
+                save_settings(self);
+                self.should_exit = true;
+                Command::none()
+            }
+            Message::ToggleSubscribed(id) => {
+                if let Some(installed) = self.installed.get_mut(&id) {
+                    installed.subscribed = !installed.subscribed;
+                }
+                save_installed_mods(self);
+                auto_update(self)
+            }
+            Message::TogglePinned(id) => {
+                if let Some(installed) = self.installed.get_mut(&id) {
+                    installed.pinned = !installed.pinned;
+                }
+                save_installed_mods(self);
+                auto_update(self)
+            }
+            Message::RollBackMod(id, version) => roll_back_mod(self, id, version),
+            Message::ModRolledBack(id, version, Ok(installed)) => {
+                let mut installed = *installed;
+                if let Some(mod_) = find_mod(self, &id) {
+                    mod_.download_status = DownloadStatus::Downloaded;
+                    installed.workshop = mod_.workshop.clone();
+                }
+                self.status_message = Some(format!(
+                    "{} is back to the version from {}, and pinned to it",
+                    installed.workshop.title,
+                    format_date(&version)
+                ));
+                self.installed.insert(id, installed);
+                save_installed_mods(self);
+                self.stored_versions = versions::list_stored_versions(&self.active_installation);
+                refresh_fingerprint(self)
+            }
+            Message::ModRolledBack(id, _, Err(err)) => {
+                // The current version may be gone already when the restore failed
+                let on_disk = self.installed.get(&id).map_or(false, |installed| {
+                    filesystem::is_mod_folder_present(&self.barony_dir_str, &installed.folder)
+                });
+                if let Some(mod_) = find_mod(self, &id) {
+                    mod_.download_status = if on_disk {
+                        DownloadStatus::Downloaded
+                    } else {
+                        DownloadStatus::ErrorOccurred(err.clone())
+                    };
+                }
+                self.status_message = Some(format!("Could not roll back: {}", err));
+                self.stored_versions = versions::list_stored_versions(&self.active_installation);
+                refresh_fingerprint(self)
+            }
+            Message::PurgeArchiveCache => Command::perform(
+                async { archive_cache::purge_cache().map_err(|err| err.to_string()) },
+                Message::ArchiveCachePurged,
+            ),
+            Message::ArchiveCachePurged(result) => {
+                if let Err(err) = result {
+                    self.status_message =
+                        Some(format!("Could not purge the download cache: {}", err));
+                }
+                read_cache_stats()
+            }
+            Message::CacheStatsRead(stats) => {
+                self.cache_stats = stats;
+                Command::none()
+            }
+            Message::InstallationSelected(name) => switch_installation(self, name),
+            Message::InstallationNameChanged(new_value) => {
+                self.installation_name_str = new_value;
+                Command::none()
+            }
+            Message::AddInstallation => {
+                let name = self.installation_name_str.trim().to_string();
+                let folder = filesystem::mod_folder_name(&name);
+                if folder.trim_matches('.').is_empty() {
+                    self.status_message = Some("Give the installation a name".to_string());
+                    return Command::none();
+                }
+                if self
+                    .installations
+                    .iter()
+                    .any(|installation| filesystem::mod_folder_name(&installation.name) == folder)
+                {
+                    self.status_message =
+                        Some(format!("There is already an installation named {}", name));
+                    return Command::none();
+                }
+                if downloads_in_progress(self) {
+                    self.status_message = Some(
+                        "Wait for the downloads to finish before switching installations"
+                            .to_string(),
+                    );
+                    return Command::none();
+                }
+
+                self.installations
+                    .push(Installation::new(name.clone(), String::new()));
+                self.installation_name_str.clear();
+                switch_installation(self, name)
+            }
+            Message::RemoveInstallation => {
+                if self.installations.len() < 2 {
+                    self.status_message =
+                        Some("The only installation can't be removed".to_string());
+                    return Command::none();
+                }
+                if downloads_in_progress(self) {
+                    self.status_message = Some(
+                        "Wait for the downloads to finish before switching installations"
+                            .to_string(),
+                    );
+                    return Command::none();
+                }
+
+                let removed = self.active_installation.clone();
+                self.installations
+                    .retain(|installation| installation.name != removed);
+                let next = self.installations[0].name.clone();
+                let command = switch_installation(self, next);
+                self.status_message = Some(format!(
+                    "Removed the {} installation, its mods folder was left as is",
+                    removed
+                ));
+                command
+            }
+            Message::ToggleWatchMod(id) => {
+                if !self.watchlist.mods.remove(&id) {
+                    self.watchlist.mods.insert(id);
+                }
+                save_watchlist(self);
+                Command::none()
+            }
+            Message::ToggleWatchTag => {
+                if let Some(PickableTag::Some(tag)) = &self.selected_tag {
+                    if !self.watchlist.tags.remove(tag) {
+                        self.watchlist.tags.insert(tag.clone());
+                    }
+                    save_watchlist(self);
+                }
+                Command::none()
+            }
+            Message::ToggleNotifications => {
+                self.show_notifications = !self.show_notifications;
+                Command::none()
+            }
+            Message::ClearNotifications => {
+                self.notifications.clear();
+                self.show_notifications = false;
+                save_notifications(self);
+                Command::none()
+            }
+            Message::ToggleCatalogReport => {
+                self.show_catalog_report = !self.show_catalog_report;
+                Command::none()
+            }
+            Message::ModsFetched(catalog) => {
+                self.catalog_diagnostics = catalog.diagnostics;
+                if self.catalog_diagnostics.is_empty() {
+                    self.show_catalog_report = false;
+                }
+
+                for mod_ in &catalog.mods {
+                    for tag in &mod_.tags {
+                        let pickable = PickableTag::Some(tag.clone());
+                        self.tags.insert(pickable);
+                    }
+                }
+
+                let catalog_ids = catalog
+                    .mods
+                    .iter()
+                    .map(|mod_| mod_.id.clone())
+                    .collect::<HashSet<_>>();
+                let catalog_folders = catalog
+                    .mods
+                    .iter()
+                    .map(|mod_| filesystem::mod_folder_name(&mod_.title))
+                    .collect::<Vec<_>>();
+
+                let first_fetch = self.mods.is_none();
+                let previous_snapshot = self.history.last().cloned();
+                record_catalog_snapshot(self, &catalog.mods);
+
+                if let Some(previous) = previous_snapshot {
+                    let mut notifications =
+                        watch_notifications(&self.watchlist, &previous, &catalog.mods);
+                    if !notifications.is_empty() {
+                        notifications.append(&mut self.notifications);
+                        notifications.truncate(MAX_NOTIFICATIONS);
+                        self.notifications = notifications;
+                        save_notifications(self);
+                    }
+                }
+                if first_fetch && !self.whats_new.is_empty() {
+                    self.status_message = Some(format!(
+                        "{} new and {} updated mods since the last launch",
+                        self.whats_new.new.len(),
+                        self.whats_new.updated.len()
+                    ));
+                }
+
+                // Installed mods that left the catalog are still shown, using the last
+                // metadata we knew about them
+                let delisted_installs = self
+                    .installed
+                    .iter()
+                    .filter(|(id, _)| !catalog_ids.contains(*id))
+                    .filter(|(id, _)| {
+                        self.mods
+                            .iter()
+                            .flatten()
+                            .all(|mod_| mod_.workshop.id != **id)
+                    })
+                    .filter(|(_, installed)| {
+                        filesystem::is_mod_folder_present(&self.barony_dir_str, &installed.folder)
+                    })
+                    .map(|(_, installed)| installed.workshop.clone())
+                    .collect::<Vec<_>>();
+
+                // Refreshing only touches what changed, so downloads in progress, loaded
+                // images and the scroll position survive it
+                let diff = diff_catalog(
+                    self.mods.iter().flatten().map(|mod_| &mod_.workshop),
+                    catalog.mods,
+                );
+
+                for id in &diff.removed {
+                    if let Some(removed_mod) = find_mod(self, id) {
+                        removed_mod.delisted = !removed_mod.outside_catalog;
+                    }
+                }
+
+                for mod_ in self.mods.iter_mut().flatten() {
+                    if catalog_ids.contains(&mod_.workshop.id) {
+                        mod_.outside_catalog = false;
+                    }
+                }
+
+                let mut newly_outdated = vec![];
+                let mut to_build = diff.added;
+                for changed in diff.changed {
+                    if let Some(installed) = self.installed.get_mut(&changed.id) {
+                        let had_update = installed.has_update();
+                        // The version on disk doesn't change with the catalog
+                        installed.installed_version = Some(installed.version());
+                        installed.workshop = changed.clone();
+                        if installed.has_update() && !had_update {
+                            newly_outdated.push(changed.title.clone());
+                        }
+                    }
+
+                    if let Some(existing) = find_mod(self, &changed.id) {
+                        existing.delisted = false;
+                        if existing.workshop.preview_url == changed.preview_url
+                            && existing.workshop.previews == changed.previews
+                        {
+                            existing.workshop = changed;
+                            continue;
+                        }
+                    }
+                    // Previews changed, so images must be downloaded again
+                    to_build.push(changed);
+                }
+
+                save_installed_mods(self);
+
+                if !newly_outdated.is_empty() {
+                    self.status_message = Some(format!(
+                        "Updates available for {}",
+                        newly_outdated.join(", ")
+                    ));
+                }
+
+                if self.mods.is_some() {
+                    self.loading_mods = false;
+                    sort_mods(self);
+                }
+
+                let mut commands = to_build
+                    .into_iter()
+                    .map(|mod_| {
+                        Command::perform(
+                            build_barony_mod(
+                                self.http_client.clone(),
+                                self.barony_dir_str.clone(),
+                                mod_,
+                            ),
+                            |m| Message::ModBuilt(Box::new(m)),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                for mod_ in delisted_installs {
+                    commands.push(Command::perform(
+                        build_barony_mod(
+                            self.http_client.clone(),
+                            self.barony_dir_str.clone(),
+                            mod_,
+                        ),
+                        |mut m| {
+                            m.delisted = true;
+                            Message::ModBuilt(Box::new(m))
+                        },
+                    ));
+                }
+
+                commands.push(scan_local_mods(self, catalog_folders));
+                commands.push(auto_update(self));
+                Command::batch(commands)
+            }
+            Message::ModBuilt(mut barony_mod) => {
+                self.loading_mods = false;
+                self.error_message = None;
+                self.removable_with_dependencies = None;
+
+                // Tracked mods may live in a folder not named after their title
+                if let Some(installed) = self.installed.get(&barony_mod.workshop.id) {
+                    if filesystem::is_mod_folder_present(&self.barony_dir_str, &installed.folder) {
+                        barony_mod.download_status = DownloadStatus::Downloaded;
+                    }
+                }
+
+                // Mods installed before the manager kept track of installs
+                if barony_mod.download_status == DownloadStatus::Downloaded
+                    && !self.installed.contains_key(&barony_mod.workshop.id)
+                {
+                    record_installed(self, &barony_mod.workshop);
+                }
+
+                // Installed subscribed mods found outdated on the first load
+                let id = barony_mod.workshop.id.clone();
+                let wants_auto_update = barony_mod.download_status == DownloadStatus::Downloaded
+                    && find_mod(self, &id).is_none()
+                    && self
+                        .installed
+                        .get(&id)
+                        .map_or(false, |installed| installed.wants_auto_update());
+
+                // Rebuilt mods keep their download state and widgets
+                if let Some(existing) = find_mod(self, &barony_mod.workshop.id) {
+                    existing.workshop = barony_mod.workshop;
+                    existing.image_handle = barony_mod.image_handle;
+                    existing.extra_previews = barony_mod.extra_previews;
+                    existing.delisted = barony_mod.delisted;
+                } else if let Some(mods) = &mut self.mods {
+                    mods.push(*barony_mod)
+                } else {
+                    self.mods = Some(vec![*barony_mod])
+                }
+
+                if wants_auto_update {
+                    start_download(self, id)
+                } else {
+                    Command::none()
+                }
+            }
+            Message::DownloadMod(id) => install_mod(self, id),
+            Message::PreparingModDownload(id, uuid) => Command::perform(
+                check_status(self.http_client.clone(), uuid.clone()),
+                move |result| match result {
+                    Ok(true) => Message::ModDownloadReady(id.clone(), uuid.clone()),
+                    Ok(false) => Message::PreparingModDownload(id.clone(), uuid.clone()),
+                    Err(err) => Message::ModDownloadError(id.clone(), err.to_string()),
+                },
+            ),
+            Message::ModDownloadReady(id, uuid) => {
+                let selected_mod = match find_mod(self, &id) {
+                    Some(selected_mod) => selected_mod,
+                    None => return Command::none(),
+                };
+
+                selected_mod.download_status = DownloadStatus::Downloading;
+                let time_updated = selected_mod.workshop.time_updated;
+                let target = ExtractTarget::new(self, &id);
+
+                Command::perform(
+                    download_mod(self.http_client.clone(), uuid),
+                    move |result| match result {
+                        Ok(zip_bytes) => {
+                            // Only archives that extract fine are worth keeping
+                            if filesystem::validate_mod_archive(&zip_bytes).is_ok() {
+                                if let Err(err) =
+                                    archive_cache::cache_archive(&id, time_updated, &zip_bytes)
+                                {
+                                    println!("Could not cache the archive of {}: {}", id, err);
+                                }
+                            }
+                            target.extract(&id, zip_bytes)
+                        }
+                        Err(err) => Message::ModDownloadError(id.clone(), err.to_string()),
+                    },
+                )
+            }
+            Message::ModDownloadError(id, msg) => {
+                dbg!(&id, &msg);
+                if let Some(selected_mod) = find_mod(self, &id) {
+                    selected_mod.download_status = DownloadStatus::ErrorOccurred(msg);
+                }
+
+                // Mods waiting for this one can't be installed anymore, and neither can
+                // the mods waiting for those
+                let mut failed = vec![id];
+                while let Some(failed_id) = failed.pop() {
+                    let waiting = self
+                        .pending_installs
+                        .iter()
+                        .filter(|(_, missing)| missing.contains(&failed_id))
+                        .map(|(waiting, _)| waiting.clone())
+                        .collect::<Vec<_>>();
+
+                    for waiting_id in waiting {
+                        self.pending_installs.remove(&waiting_id);
+                        if let Some(waiting_mod) = find_mod(self, &waiting_id) {
+                            waiting_mod.download_status = DownloadStatus::ErrorOccurred(format!(
+                                "could not install the required item {}",
+                                failed_id
+                            ));
+                        }
+                        failed.push(waiting_id);
+                    }
+                }
+
+                Command::none()
+            }
+            Message::ModDownloaded(id) => {
+                if let Some(selected_mod) = find_mod(self, &id) {
+                    selected_mod.download_status = DownloadStatus::Downloaded;
+                    let workshop = selected_mod.workshop.clone();
+                    record_installed(self, &workshop);
+                }
+
+                // Start the mods that were only waiting for this one
+                let mut ready = vec![];
+                for (waiting, missing) in self.pending_installs.iter_mut() {
+                    missing.remove(&id);
+                    if missing.is_empty() {
+                        ready.push(waiting.clone());
+                    }
+                }
+
+                self.stored_versions = versions::list_stored_versions(&self.active_installation);
+
+                let mut commands = vec![refresh_fingerprint(self), read_cache_stats()];
+                for waiting in ready {
+                    self.pending_installs.remove(&waiting);
+                    commands.push(start_download(self, waiting));
+                }
+                Command::batch(commands)
+            }
+            Message::RemoveMod(id) => {
+                let dependents = self
+                    .mods
+                    .as_ref()
+                    .map(|mods| downloaded_dependents(&id, mods))
+                    .unwrap_or_default();
+
+                if dependents.is_empty() {
+                    remove_mods(self, vec![id])
+                } else {
+                    self.removal_prompt = Some(RemovalPrompt { id, dependents });
+                    Command::none()
+                }
+            }
+            Message::ConfirmRemoveMod(id) => {
+                self.removal_prompt = None;
+                remove_mods(self, vec![id])
+            }
+            Message::ModRemoved(id, result) => {
+                let status = match result {
+                    Ok(_) => {
+                        self.installed.remove(&id);
+                        DownloadStatus::NotDownloaded
+                    }
+                    Err(err) => DownloadStatus::ErrorOccurred(err),
+                };
+                if let Some(selected_mod) = find_mod(self, &id) {
+                    selected_mod.download_status = status;
+                }
+
+                save_installed_mods(self);
+                self.stored_versions = versions::list_stored_versions(&self.active_installation);
+                refresh_fingerprint(self)
+            }
+            Message::RemoveModWithDependencies(id) => {
+                self.removal_prompt = None;
+                let mut ids = self
+                    .mods
+                    .as_ref()
+                    .map(|mods| unused_dependencies(&id, mods))
+                    .unwrap_or_default();
+                ids.insert(0, id);
+                remove_mods(self, ids)
+            }
+            Message::CancelRemoval => {
+                self.removal_prompt = None;
+                Command::none()
+            }
+            Message::KeepDelistedMod(id) => {
+                if let Some(installed) = self.installed.get_mut(&id) {
+                    installed.keep_delisted = true;
+                    save_installed_mods(self);
+                }
+                Command::none()
+            }
+            Message::BackupMod(id) => {
+                let installed = match self.installed.get(&id) {
+                    Some(installed) => installed,
+                    None => return Command::none(),
+                };
+
+                // Mods can be big, so they are copied in the background
+                self.status_message =
+                    Some(format!("Backing up \"{}\"...", installed.workshop.title));
+                let barony_dir = self.barony_dir_str.clone();
+                let folder = installed.folder.clone();
+                Command::perform(
+                    async move {
+                        filesystem::backup_mod(&barony_dir, &folder).map_err(|err| err.to_string())
+                    },
+                    move |result| Message::ModBackedUp(id.clone(), result),
+                )
+            }
+            Message::ModBackedUp(id, result) => {
+                let title = self
+                    .installed
+                    .get(&id)
+                    .map_or(id, |installed| installed.workshop.title.clone());
+                self.status_message = match result {
+                    Ok(path) => Some(format!("\"{}\" was backed up to {}", title, path.display())),
+                    Err(err) => Some(format!("Could not back up \"{}\": {}", title, err)),
+                };
+                Command::none()
+            }
+            Message::ErrorHappened(msg) => {
+                self.error_message = Some(format!("An error occurred: {}", msg));
+                Command::none()
+            }
+        }
+    }
+
+    // TODO: Clean this view
+    fn view(&mut self) -> Element<Self::Message> {
+        let removal_message = self.removal_prompt.as_ref().map(|prompt| {
+            let title_of = |id: &String| {
+                self.mods
+                    .iter()
+                    .flatten()
+                    .find(|mod_| mod_.workshop.id == *id)
+                    .map_or(id.clone(), |mod_| mod_.workshop.title.clone())
+            };
+
+            let dependents = prompt.dependents.iter().map(title_of).collect::<Vec<_>>();
+            let message = format!(
+                "\"{}\" is required by: {}. Remove it anyway?",
+                title_of(&prompt.id),
+                dependents.join(", ")
+            );
+
+            (prompt.id.clone(), message)
+        });
+
+        // ------------------ Header -----------------------
+        let app_name = Text::new(format!("Barony Mod Manager {}", VERSION))
+            .size(30)
+            .width(Length::FillPortion(3))
+            .color(iced::Color::WHITE);
+
+        let mod_search_input = TextInput::new(
+            &mut self.mod_search_input,
+            "Search for mods...",
+            &self.query,
+            Message::ModSearchInputChanged,
+        )
+        .padding(5)
+        .width(Length::FillPortion(2))
+        .style(GeneralUiStyles)
+        .size(20);
+
+        let installation_names = self
+            .installations
+            .iter()
+            .map(|installation| installation.name.clone())
+            .collect::<Vec<_>>();
+        let installation_pick_list = PickList::new(
+            &mut self.installation_picklist,
+            installation_names,
+            Some(self.active_installation.clone()),
+            Message::InstallationSelected,
+        )
+        .text_size(20)
+        .style(GeneralUiStyles);
+
+        let mut header = Row::new()
+            .align_items(Align::Center)
+            .push(app_name)
+            .push(Row::new().padding(10).push(installation_pick_list));
+
+        if let Some(fingerprint) = &self.fingerprint {
+            let fingerprint_text = Text::new(format!("Mod set: {}", fingerprint))
+                .size(20)
+                .color(Color::WHITE);
+            let copy_button = Button::new(&mut self.copy_fingerprint_button, Text::new("Copy"))
+                .style(GeneralUiStyles)
+                .on_press(Message::CopyFingerprint);
+
+            header = header.push(
+                Row::new()
+                    .spacing(10)
+                    .padding(10)
+                    .align_items(Align::Center)
+                    .push(fingerprint_text)
+                    .push(copy_button),
+            );
+        }
+
+        let header = header.push(mod_search_input);
+
+        // ------------------ Bottom inputs -----------------------
+
+        let path_label_message = format!(
+            "Barony directory of {} {}",
+            self.active_installation,
+            if self.barony_dir_valid {
+                "(VALID)"
+            } else {
+                "(INVALID)"
+            }
+        );
 
         let barony_path_label = Text::new(path_label_message).size(20).color(Color::WHITE);
         let barony_path_input = TextInput::new(
@@ -388,16 +2217,410 @@ impl Application for BaronyModManager {
         .style(GeneralUiStyles)
         .size(20);
 
+        let cache_label = Text::new(format!(
+            "Download cache: {} archives, {:.1} MB",
+            self.cache_stats.archives,
+            self.cache_stats.bytes as f64 / (1024.0 * 1024.0)
+        ))
+        .size(16)
+        .color(Color::WHITE);
+        let purge_cache_button = Button::new(&mut self.purge_cache_button, Text::new("Purge"))
+            .style(RemoveModButton)
+            .on_press(Message::PurgeArchiveCache);
+
+        let installation_name_input = TextInput::new(
+            &mut self.installation_name_input,
+            "Name of another installation",
+            &self.installation_name_str,
+            Message::InstallationNameChanged,
+        )
+        .padding(5)
+        .style(GeneralUiStyles)
+        .size(20)
+        .on_submit(Message::AddInstallation);
+        let add_installation_button =
+            Button::new(&mut self.add_installation_button, Text::new("Add"))
+                .style(DownloadModButton)
+                .on_press(Message::AddInstallation);
+        let mut remove_installation_button = Button::new(
+            &mut self.remove_installation_button,
+            Text::new("Remove this one"),
+        )
+        .style(RemoveModButton);
+        if self.installations.len() > 1 {
+            remove_installation_button =
+                remove_installation_button.on_press(Message::RemoveInstallation);
+        }
+
         let barony_path_section = Column::new()
             .spacing(10)
             .max_width(600)
             .push(barony_path_label)
-            .push(barony_path_input);
+            .push(barony_path_input)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(installation_name_input)
+                    .push(add_installation_button)
+                    .push(remove_installation_button),
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(cache_label)
+                    .push(purge_cache_button),
+            );
+
+        let archive_label = Text::new("Install from a zip archive (or drop it on the window)")
+            .size(20)
+            .color(Color::WHITE);
+        let archive_path_input = TextInput::new(
+            &mut self.archive_path_input,
+            "Path to the archive",
+            &self.archive_path_str,
+            Message::ArchivePathChanged,
+        )
+        .padding(5)
+        .style(GeneralUiStyles)
+        .size(20)
+        .on_submit(Message::InstallArchive(PathBuf::from(
+            &self.archive_path_str,
+        )));
+
+        let install_archive_button =
+            Button::new(&mut self.install_archive_button, Text::new("Install"))
+                .style(DownloadModButton)
+                .on_press(Message::InstallArchive(PathBuf::from(
+                    &self.archive_path_str,
+                )));
+        let choose_archive_button =
+            Button::new(&mut self.choose_archive_button, Text::new("Browse..."))
+                .style(GeneralUiStyles)
+                .on_press(Message::OpenArchiveChooser);
+
+        let bundle_label = Text::new(format!(
+            "Offline bundle ({} selected)",
+            self.bundle_selection.len()
+        ))
+        .size(20)
+        .color(Color::WHITE);
+        let bundle_path_input = TextInput::new(
+            &mut self.bundle_path_input,
+            "Path to the bundle file",
+            &self.bundle_path_str,
+            Message::BundlePathChanged,
+        )
+        .padding(5)
+        .style(GeneralUiStyles)
+        .size(20);
+
+        let export_bundle_button = Button::new(&mut self.export_bundle_button, Text::new("Export"))
+            .style(GeneralUiStyles)
+            .on_press(Message::ExportBundle);
+
+        let import_bundle_button = Button::new(&mut self.import_bundle_button, Text::new("Import"))
+            .style(DownloadModButton)
+            .on_press(Message::ImportBundle);
+
+        let archive_section = Column::new()
+            .spacing(10)
+            .max_width(600)
+            .push(archive_label)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(archive_path_input)
+                    .push(choose_archive_button)
+                    .push(install_archive_button),
+            )
+            .push(bundle_label)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(bundle_path_input)
+                    .push(export_bundle_button)
+                    .push(import_bundle_button),
+            );
+
+        let modpack_label = Text::new("Modpack").size(20).color(Color::WHITE);
+        let modpack_path_input = TextInput::new(
+            &mut self.modpack_path_input,
+            "Path to the modpack file",
+            &self.modpack_path_str,
+            Message::ModpackPathChanged,
+        )
+        .padding(5)
+        .style(GeneralUiStyles)
+        .size(20);
+
+        let modpack_name_input = TextInput::new(
+            &mut self.modpack_name_input,
+            "Name (optional)",
+            &self.modpack_name_str,
+            Message::ModpackNameChanged,
+        )
+        .padding(5)
+        .width(Length::Units(160))
+        .style(GeneralUiStyles)
+        .size(20);
+
+        let modpack_description_input = TextInput::new(
+            &mut self.modpack_description_input,
+            "Description of the exported modpack (optional)",
+            &self.modpack_description_str,
+            Message::ModpackDescriptionChanged,
+        )
+        .padding(5)
+        .style(GeneralUiStyles)
+        .size(20);
+
+        let export_modpack_button =
+            Button::new(&mut self.export_modpack_button, Text::new("Export"))
+                .style(GeneralUiStyles)
+                .on_press(Message::ExportModpack);
+
+        let import_modpack_button =
+            Button::new(&mut self.import_modpack_button, Text::new("Import"))
+                .style(DownloadModButton)
+                .on_press(Message::ImportModpack);
+
+        let modpack_section = Column::new()
+            .spacing(10)
+            .max_width(600)
+            .push(modpack_label)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(modpack_path_input)
+                    .push(modpack_name_input)
+                    .push(export_modpack_button)
+                    .push(import_modpack_button),
+            )
+            .push(modpack_description_input);
+
+        let prune_checkbox = Checkbox::new(
+            self.prune_mods,
+            "Remove mods not in the modpack",
+            Message::PruneModsToggled,
+        )
+        .style(GeneralUiStyles);
+
+        let plan_button = Button::new(&mut self.plan_button, Text::new("Plan"))
+            .style(GeneralUiStyles)
+            .on_press(Message::PlanModpack);
+
+        let mut apply_plan_button =
+            Button::new(&mut self.apply_plan_button, Text::new("Apply")).style(DownloadModButton);
+        if self.plan.is_some() {
+            apply_plan_button = apply_plan_button.on_press(Message::ApplyPlan);
+        }
+
+        let modpack_section = modpack_section.push(
+            Row::new()
+                .spacing(10)
+                .align_items(Align::Center)
+                .push(prune_checkbox)
+                .push(plan_button)
+                .push(apply_plan_button),
+        );
+
+        let lock_path_input = TextInput::new(
+            &mut self.lock_path_input,
+            "Path to the lock file",
+            &self.lock_path_str,
+            Message::LockPathChanged,
+        )
+        .padding(5)
+        .style(GeneralUiStyles)
+        .size(20);
+
+        let write_lock_button = Button::new(&mut self.write_lock_button, Text::new("Lock"))
+            .style(GeneralUiStyles)
+            .on_press(Message::WriteLockFile);
+
+        let verify_lock_button = Button::new(&mut self.verify_lock_button, Text::new("Verify"))
+            .style(GeneralUiStyles)
+            .on_press(Message::VerifyLockFile);
+
+        let compare_button = Button::new(&mut self.compare_button, Text::new("Compare"))
+            .style(GeneralUiStyles)
+            .on_press(Message::CompareModList);
+
+        let modpack_section = modpack_section.push(
+            Row::new()
+                .spacing(10)
+                .align_items(Align::Center)
+                .push(lock_path_input)
+                .push(write_lock_button)
+                .push(verify_lock_button)
+                .push(compare_button),
+        );
+
+        let share_label = match self.share_port {
+            Some(port) => format!("Stop sharing (port {})", port),
+            None => "Share my mods".to_string(),
+        };
+        let share_button = Button::new(&mut self.share_button, Text::new(share_label))
+            .style(GeneralUiStyles)
+            .on_press(Message::ToggleSharing);
+
+        let peer_address_input = TextInput::new(
+            &mut self.peer_address_input,
+            "Peer address (ip:port)",
+            &self.peer_address_str,
+            Message::PeerAddressChanged,
+        )
+        .padding(5)
+        .style(GeneralUiStyles)
+        .size(20)
+        .on_submit(Message::ConnectPeer);
+
+        let connect_peer_button = Button::new(&mut self.connect_peer_button, Text::new("Connect"))
+            .style(GeneralUiStyles)
+            .on_press(Message::ConnectPeer);
+
+        let find_peers_button = Button::new(&mut self.find_peers_button, Text::new("Find peers"))
+            .style(GeneralUiStyles)
+            .on_press(Message::FindPeers);
+
+        let collection_input = TextInput::new(
+            &mut self.collection_input,
+            "Collection id or url",
+            &self.collection_input_str,
+            Message::CollectionInputChanged,
+        )
+        .padding(5)
+        .style(GeneralUiStyles)
+        .size(20)
+        .on_submit(Message::AddCollection);
+
+        let add_collection_button = Button::new(&mut self.add_collection_button, Text::new("Add"))
+            .style(DownloadModButton)
+            .on_press(Message::AddCollection);
+
+        let pasted_ids_input = TextInput::new(
+            &mut self.pasted_ids_input,
+            "Workshop urls or ids",
+            &self.pasted_ids_str,
+            Message::PastedIdsChanged,
+        )
+        .padding(5)
+        .style(GeneralUiStyles)
+        .size(20)
+        .on_submit(Message::InstallPastedIds);
+
+        let install_pasted_button =
+            Button::new(&mut self.install_pasted_button, Text::new("Install"))
+                .style(DownloadModButton)
+                .on_press(Message::InstallPastedIds);
+
+        let collections_section = Column::new()
+            .spacing(10)
+            .max_width(400)
+            .push(
+                Text::new("Install by url or id")
+                    .size(20)
+                    .color(Color::WHITE),
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(pasted_ids_input)
+                    .push(install_pasted_button),
+            )
+            .push(Text::new("Collections").size(20).color(Color::WHITE))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(collection_input)
+                    .push(add_collection_button),
+            );
+
+        let steam_api_key_input = TextInput::new(
+            &mut self.steam_api_key_input,
+            "Steam Web API key (optional)",
+            &self.steam_api_key_str,
+            Message::SteamApiKeyChanged,
+        )
+        .padding(5)
+        .style(GeneralUiStyles)
+        .size(20)
+        .password();
+
+        let steam_api_url_input = TextInput::new(
+            &mut self.steam_api_url_input,
+            "https://api.steampowered.com",
+            &self.steam_api_url_str,
+            Message::SteamApiUrlChanged,
+        )
+        .padding(5)
+        .style(GeneralUiStyles)
+        .size(20);
+
+        let collections_section =
+            self.collections
+                .iter_mut()
+                .fold(collections_section, |section, tracked| {
+                    let id = tracked.collection.id.clone();
+                    let label = Text::new(format!(
+                        "{} ({} items)",
+                        tracked.collection.title,
+                        tracked.collection.items.len()
+                    ))
+                    .color(Color::WHITE)
+                    .width(Length::Fill);
+
+                    let sync_button = Button::new(&mut tracked.sync_button, Text::new("Sync"))
+                        .style(GeneralUiStyles)
+                        .on_press(Message::SyncCollection(id.clone()));
+                    let forget_button =
+                        Button::new(&mut tracked.forget_button, Text::new("Forget"))
+                            .style(RemoveModButton)
+                            .on_press(Message::ForgetCollection(id));
+
+                    section.push(
+                        Row::new()
+                            .spacing(10)
+                            .align_items(Align::Center)
+                            .push(label)
+                            .push(sync_button)
+                            .push(forget_button),
+                    )
+                });
+        let collections_section = collections_section
+            .push(Text::new("Steam Web API").size(20).color(Color::WHITE))
+            .push(steam_api_key_input)
+            .push(steam_api_url_input);
+
+        let lan_section = Column::new()
+            .spacing(10)
+            .max_width(400)
+            .push(Text::new("Local network").size(20).color(Color::WHITE))
+            .push(share_button)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .align_items(Align::Center)
+                    .push(peer_address_input)
+                    .push(connect_peer_button)
+                    .push(find_peers_button),
+            );
 
         let bottom_inputs = Row::new()
             .push(barony_path_section)
+            .push(archive_section)
+            .push(modpack_section)
+            .push(collections_section)
+            .push(lan_section)
             .align_items(Align::End)
-            .spacing(100);
+            .spacing(30);
 
         // ---------------- Filtering/Sorting ------------------
         let filter_picklist_label = Text::new("Filter:").color(Color::WHITE);
@@ -455,24 +2678,95 @@ impl Application for BaronyModManager {
         .style(GeneralUiStyles);
 
         let tag_pick_list_label = Text::new("Tag:").color(Color::WHITE);
-        let tag_pick_list = Row::new()
+        let mut tag_pick_list = Row::new()
             .spacing(10)
             .align_items(Align::Center)
             .push(tag_pick_list_label)
             .push(pick_list_tags);
 
+        if let Some(PickableTag::Some(tag)) = &self.selected_tag {
+            let watch_label = if self.watchlist.tags.contains(tag) {
+                "Unwatch tag"
+            } else {
+                "Watch tag"
+            };
+            let watch_tag_button = Button::new(&mut self.watch_tag_button, Text::new(watch_label))
+                .style(GeneralUiStyles)
+                .on_press(Message::ToggleWatchTag);
+            tag_pick_list = tag_pick_list.push(watch_tag_button);
+        }
+
         let pick_list_full = Row::new()
             .spacing(10)
             .align_items(Align::Center)
             .push(pick_list_label)
             .push(pick_list);
 
-        let refresh_button = Button::new(&mut self.search_button_state, Text::new("Refresh"))
+        let refresh_label = if self.loading_mods {
+            "Refreshing..."
+        } else {
+            "Refresh"
+        };
+        let refresh_button = Button::new(&mut self.search_button_state, Text::new(refresh_label))
             .style(GeneralUiStyles)
             .width(Length::Shrink)
             .on_press(Message::LoadMods);
 
-        let refresh_section = Row::new().width(Length::Shrink).push(refresh_button);
+        let mut refresh_section = Row::new().width(Length::Shrink).spacing(10);
+
+        let notifications_label = if self.show_notifications {
+            "Hide notifications".to_string()
+        } else {
+            format!("Notifications ({})", self.notifications.len())
+        };
+        let notifications_button = Button::new(
+            &mut self.notifications_button,
+            Text::new(notifications_label),
+        )
+        .style(GeneralUiStyles)
+        .on_press(Message::ToggleNotifications);
+        refresh_section = refresh_section.push(notifications_button);
+
+        if !self.catalog_diagnostics.is_empty() {
+            let report_label = if self.show_catalog_report {
+                "Hide catalog report".to_string()
+            } else {
+                format!(
+                    "Catalog report ({} skipped, {} repaired)",
+                    self.catalog_diagnostics.rejected.len(),
+                    self.catalog_diagnostics.repaired.len()
+                )
+            };
+
+            let report_button =
+                Button::new(&mut self.catalog_report_button, Text::new(report_label))
+                    .style(GeneralUiStyles)
+                    .on_press(Message::ToggleCatalogReport);
+
+            refresh_section = refresh_section.push(report_button);
+        }
+
+        let refresh_installed_button = Button::new(
+            &mut self.refresh_installed_button,
+            Text::new("Refresh installed"),
+        )
+        .style(GeneralUiStyles)
+        .width(Length::Shrink)
+        .on_press(Message::RefreshInstalledDetails);
+
+        let refresh_interval_list = PickList::new(
+            &mut self.refresh_interval_picklist,
+            &RefreshInterval::ALL[..],
+            Some(self.refresh_interval),
+            Message::RefreshIntervalSelected,
+        )
+        .text_size(20)
+        .style(GeneralUiStyles);
+
+        let refresh_section = refresh_section
+            .push(refresh_installed_button)
+            .push(refresh_interval_list)
+            .push(refresh_button);
 
         let search_options_ = Row::new()
             .spacing(20)
@@ -494,7 +2788,7 @@ impl Application for BaronyModManager {
                 .width(Length::Fill)
                 .align_x(Align::Center)
                 .align_y(Align::Center)
-        } else if self.loading_mods {
+        } else if self.loading_mods && self.mods.is_none() {
             let text = Text::new("Loading mods...").color(Color::WHITE).size(35);
 
             Container::new(text)
@@ -502,6 +2796,187 @@ impl Application for BaronyModManager {
                 .align_y(Align::Center)
                 .width(Length::Fill)
                 .height(Length::Fill)
+        } else if let Some(chooser) = &mut self.archive_chooser {
+            let mut controls = Row::new().spacing(10).align_items(Align::Center).push(
+                Text::new(format!("Choose a zip archive in {}", chooser.dir.display()))
+                    .color(Color::WHITE),
+            );
+            if let Some(parent) = chooser.dir.parent() {
+                controls = controls.push(
+                    Button::new(&mut chooser.parent_button, Text::new("Parent folder"))
+                        .style(GeneralUiStyles)
+                        .on_press(Message::ArchiveChooserDir(parent.to_path_buf())),
+                );
+            }
+            controls = controls.push(
+                Button::new(&mut chooser.close_button, Text::new("Cancel"))
+                    .style(RemoveModButton)
+                    .on_press(Message::CloseArchiveChooser),
+            );
+
+            let entries = Scrollable::new(&mut self.mods_scrollable)
+                .padding(15)
+                .spacing(10)
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .push(controls);
+
+            let entries = if chooser.entries.is_empty() {
+                entries.push(Text::new("No folders or zip archives here").color(Color::WHITE))
+            } else {
+                chooser
+                    .entries
+                    .iter_mut()
+                    .fold(entries, |entries, (entry, state)| {
+                        let name = entry
+                            .path
+                            .file_name()
+                            .map(|name| name.to_string_lossy().to_string())
+                            .unwrap_or_default();
+                        let (label, message) = if entry.is_dir {
+                            (
+                                format!("{}/", name),
+                                Message::ArchiveChooserDir(entry.path.clone()),
+                            )
+                        } else {
+                            (name, Message::ArchiveChosen(entry.path.clone()))
+                        };
+                        entries.push(
+                            Button::new(state, Text::new(label))
+                                .style(GeneralUiStyles)
+                                .on_press(message),
+                        )
+                    })
+            };
+
+            Container::new(entries).height(Length::Fill)
+        } else if self.show_notifications {
+            let clear_button = Button::new(
+                &mut self.clear_notifications_button,
+                Text::new("Clear notifications"),
+            )
+            .style(GeneralUiStyles)
+            .on_press(Message::ClearNotifications);
+
+            let watched_tags = if self.watchlist.tags.is_empty() {
+                "No watched tags, pick a tag to watch it".to_string()
+            } else {
+                format!(
+                    "Watched tags: {}",
+                    self.watchlist
+                        .tags
+                        .iter()
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            };
+
+            let notifications = Scrollable::new(&mut self.mods_scrollable)
+                .padding(15)
+                .spacing(10)
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .push(clear_button)
+                .push(Text::new(watched_tags).color(Color::WHITE));
+
+            let notifications = if self.notifications.is_empty() {
+                notifications.push(
+                    Text::new("Nothing new about the watched mods and tags").color(Color::WHITE),
+                )
+            } else {
+                self.notifications
+                    .iter()
+                    .fold(notifications, |notifications, notification| {
+                        notifications.push(
+                            Text::new(format!(
+                                "{}  {}",
+                                format_date(&notification.time),
+                                notification
+                            ))
+                            .color(Color::WHITE),
+                        )
+                    })
+            };
+
+            Container::new(notifications).height(Length::Fill)
+        } else if self.show_catalog_report {
+            let diagnostics = &self.catalog_diagnostics;
+            let report = Scrollable::new(&mut self.mods_scrollable)
+                .padding(15)
+                .spacing(10)
+                .width(Length::Fill)
+                .height(Length::Fill);
+
+            let report = diagnostics.rejected.iter().fold(report, |report, issue| {
+                report.push(Text::new(format!("Skipped: {}", issue)).color(Color::WHITE))
+            });
+
+            let report = diagnostics.repaired.iter().fold(report, |report, issue| {
+                report.push(Text::new(format!("Repaired: {}", issue)).color(Color::WHITE))
+            });
+
+            Container::new(report).height(Length::Fill)
+        } else if let Some(differences) = &self.comparison {
+            let close_button = Button::new(
+                &mut self.close_comparison_button,
+                Text::new("Close comparison"),
+            )
+            .style(GeneralUiStyles)
+            .on_press(Message::CloseComparison);
+
+            let comparison_row = |title: String, mine: String, theirs: String| {
+                Row::new()
+                    .spacing(20)
+                    .push(
+                        Text::new(title)
+                            .width(Length::FillPortion(3))
+                            .color(Color::WHITE),
+                    )
+                    .push(
+                        Text::new(mine)
+                            .width(Length::FillPortion(1))
+                            .color(Color::WHITE),
+                    )
+                    .push(
+                        Text::new(theirs)
+                            .width(Length::FillPortion(1))
+                            .color(Color::WHITE),
+                    )
+            };
+
+            let comparison = Scrollable::new(&mut self.mods_scrollable)
+                .padding(15)
+                .spacing(10)
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .push(close_button)
+                .push(comparison_row(
+                    format!("{} mods differ", differences.len()),
+                    "Yours".to_string(),
+                    "Theirs".to_string(),
+                ));
+
+            let comparison = differences
+                .iter()
+                .fold(comparison, |comparison, difference| {
+                    let version = |date: Option<DateTime<Utc>>| {
+                        date.map_or("Not installed".to_string(), |date| format_date(&date))
+                    };
+                    let theirs = if difference.files_differ {
+                        "Other files".to_string()
+                    } else {
+                        version(difference.theirs)
+                    };
+
+                    comparison.push(comparison_row(
+                        format!("{} ({})", difference.title, difference.id),
+                        version(difference.mine),
+                        theirs,
+                    ))
+                });
+
+            Container::new(comparison).height(Length::Fill)
         } else if self.mods.is_none() {
             let text = Text::new(
                 "You have not loaded the available mods yet. Click the \"Refresh\" button to load them.",
@@ -522,6 +2997,87 @@ impl Application for BaronyModManager {
                     .width(Length::Fill)
                     .height(Length::Fill);
 
+                let query = self.query.to_lowercase();
+                let show_local_mods =
+                    matches!(
+                        self.selected_filter,
+                        Some(Filter::None) | Some(Filter::Downloaded) | None
+                    ) && matches!(self.selected_tag, Some(PickableTag::None) | None);
+
+                let local_mods = self
+                    .local_mods
+                    .iter_mut()
+                    .filter(|local| show_local_mods && local.folder.to_lowercase().contains(&query))
+                    .collect::<Vec<_>>();
+
+                let mods_scrollable = if local_mods.is_empty() {
+                    mods_scrollable
+                } else {
+                    let label = Text::new("Local").size(25).color(Color::WHITE);
+                    local_mods
+                        .into_iter()
+                        .fold(mods_scrollable.push(label), |scroll, local| {
+                            scroll.push(local_mod_card(local))
+                        })
+                        .push(Text::new("Workshop").size(25).color(Color::WHITE))
+                };
+
+                let removable_with_dependencies = self
+                    .removable_with_dependencies
+                    .get_or_insert_with(|| removable_with_dependencies(mods));
+
+                let peer_mod_ids = self
+                    .peer
+                    .iter()
+                    .flat_map(|peer| peer.mods.iter().map(|shared| shared.id.clone()))
+                    .collect::<HashSet<_>>();
+
+                let bundle_selection = &self.bundle_selection;
+                let whats_new = &self.whats_new;
+                let watched = &self.watchlist.mods;
+                let outdated = self
+                    .installed
+                    .iter()
+                    .filter(|(_, installed)| installed.has_update())
+                    .map(|(id, _)| id.clone())
+                    .collect::<HashSet<_>>();
+                let trends = &self.trends;
+
+                let subscribed = self
+                    .installed
+                    .iter()
+                    .filter(|(_, installed)| installed.subscribed)
+                    .map(|(id, _)| id.clone())
+                    .collect::<HashSet<_>>();
+                let pinned = self
+                    .installed
+                    .iter()
+                    .filter(|(_, installed)| installed.pinned)
+                    .map(|(id, _)| id.clone())
+                    .collect::<HashSet<_>>();
+
+                // The newest stored version that's not the installed one
+                let installs = &self.installed;
+                let rollback_targets = self
+                    .stored_versions
+                    .iter()
+                    .filter_map(|(id, stored)| {
+                        let current = installs.get(id).map(|installed| installed.version());
+                        stored
+                            .iter()
+                            .map(|stored| stored.version())
+                            .find(|version| Some(*version) != current)
+                            .map(|version| (id.clone(), version))
+                    })
+                    .collect::<HashMap<_, _>>();
+
+                let kept_delisted = self
+                    .installed
+                    .iter()
+                    .filter(|(_, installed)| installed.keep_delisted)
+                    .map(|(id, _)| id.clone())
+                    .collect::<HashSet<_>>();
+
                 // TODO: Don't filter mods every render (0.3.4 planned)
                 let download_filtered = if let Some(filter) = &self.selected_filter {
                     mods.iter_mut()
@@ -535,7 +3091,16 @@ impl Application for BaronyModManager {
                             Filter::Downloading => {
                                 mod_.download_status == DownloadStatus::Downloading
                                     || mod_.download_status == DownloadStatus::Preparing
+                                    || mod_.download_status
+                                        == DownloadStatus::WaitingForDependencies
+                            }
+                            Filter::WithDependencies => !mod_.workshop.children.is_empty(),
+                            Filter::NewSinceLastLaunch => whats_new.new.contains(&mod_.workshop.id),
+                            Filter::RecentlyUpdated => {
+                                whats_new.updated.contains(&mod_.workshop.id)
                             }
+                            Filter::Watched => watched.contains(&mod_.workshop.id),
+                            Filter::UpdateAvailable => outdated.contains(&mod_.workshop.id),
                             Filter::None => true,
                         })
                         .collect::<Vec<_>>()
@@ -569,6 +3134,7 @@ impl Application for BaronyModManager {
                         .filter(|mod_| {
                             mod_.workshop.title.to_lowercase().contains(&query)
                                 || mod_.workshop.description.to_lowercase().contains(&query)
+                                || mod_.workshop.creator.to_lowercase().contains(&query)
                         })
                         .collect::<Vec<_>>()
                 } else {
@@ -580,11 +3146,28 @@ impl Application for BaronyModManager {
                     .fold(mods_scrollable, |scroll, mod_| {
                         let mod_image = Image::new(mod_.image_handle.clone());
 
-                        let views_label = Text::new(format!("Views: {}", mod_.workshop.views))
-                            .color(Color::WHITE);
+                        let trend = trends.get(&mod_.workshop.id).copied().unwrap_or_default();
+                        let growth = |value: i64| {
+                            if value > 0 {
+                                format!(" (+{})", value)
+                            } else {
+                                String::new()
+                            }
+                        };
 
-                        let votes_up_label = Text::new(format!("Up: {}", mod_.workshop.votes.up))
-                            .color(Color::WHITE);
+                        let views_label = Text::new(format!(
+                            "Views: {}{}",
+                            mod_.workshop.views,
+                            growth(trend.views)
+                        ))
+                        .color(Color::WHITE);
+
+                        let votes_up_label = Text::new(format!(
+                            "Up: {}{}",
+                            mod_.workshop.votes.up,
+                            growth(trend.votes)
+                        ))
+                        .color(Color::WHITE);
 
                         let votes_down_label =
                             Text::new(format!("Down: {}", mod_.workshop.votes.down))
@@ -595,40 +3178,189 @@ impl Application for BaronyModManager {
                             .push(votes_up_label)
                             .push(votes_down_label);
 
-                        // TODO: Create function for this
+                        let favorites_label =
+                            Text::new(format!("Favorites: {}", mod_.workshop.favorited))
+                                .color(Color::WHITE);
+
+                        let subscriptions_label =
+                            Text::new(format!("Subscribers: {}", mod_.workshop.subscriptions))
+                                .color(Color::WHITE);
+
                         let created_at = Text::new(format!(
-                            "Created: {}/{}/{}",
-                            mod_.workshop.time_created.day(),
-                            mod_.workshop.time_created.month(),
-                            mod_.workshop.time_created.year()
+                            "Created: {}",
+                            format_date(&mod_.workshop.time_created)
                         ))
                         .color(Color::WHITE);
 
                         let last_updated_at = Text::new(format!(
-                            "Updated: {}/{}/{}",
-                            mod_.workshop.time_updated.day(),
-                            mod_.workshop.time_updated.month(),
-                            mod_.workshop.time_updated.year()
+                            "Updated: {}",
+                            format_date(&mod_.workshop.time_updated)
                         ))
                         .color(Color::WHITE);
 
                         let download_or_remove_button = match mod_.download_status {
+                            // Delisted mods can't be fetched anymore
+                            DownloadStatus::NotDownloaded | DownloadStatus::ErrorOccurred(_)
+                                if mod_.delisted =>
+                            {
+                                Button::new(&mut mod_.download_button, Text::new("Unavailable"))
+                                    .style(DownloadingModButton)
+                            }
                             // An error occured or the mod is not downloaded, can try again
                             DownloadStatus::NotDownloaded | DownloadStatus::ErrorOccurred(_) => {
                                 Button::new(&mut mod_.download_button, Text::new("Download"))
                                     .style(DownloadModButton)
                                     .on_press(Message::DownloadMod(mod_.workshop.id.clone()))
                             }
-                            DownloadStatus::Downloading | DownloadStatus::Preparing => {
+                            DownloadStatus::Downloading
+                            | DownloadStatus::Preparing
+                            | DownloadStatus::WaitingForDependencies => {
                                 Button::new(&mut mod_.download_button, Text::new("Downloading"))
                                     .style(DownloadingModButton)
                             }
+                            DownloadStatus::Removing => {
+                                Button::new(&mut mod_.download_button, Text::new("Removing"))
+                                    .style(DownloadingModButton)
+                            }
+                            DownloadStatus::RollingBack => {
+                                Button::new(&mut mod_.download_button, Text::new("Rolling back"))
+                                    .style(DownloadingModButton)
+                            }
                             _ => Button::new(&mut mod_.download_button, Text::new("Remove"))
                                 .style(RemoveModButton)
                                 .on_press(Message::RemoveMod(mod_.workshop.id.clone())),
                         };
 
-                        let buttons_row = Column::new().spacing(10).push(download_or_remove_button);
+                        let watch_label = if watched.contains(&mod_.workshop.id) {
+                            "Unwatch"
+                        } else {
+                            "Watch"
+                        };
+                        let watch_button =
+                            Button::new(&mut mod_.watch_button, Text::new(watch_label))
+                                .style(GeneralUiStyles)
+                                .on_press(Message::ToggleWatchMod(mod_.workshop.id.clone()));
+
+                        let mut buttons_row = Column::new()
+                            .spacing(10)
+                            .push(download_or_remove_button)
+                            .push(watch_button);
+
+                        let can_get_from_peer = matches!(
+                            mod_.download_status,
+                            DownloadStatus::NotDownloaded | DownloadStatus::ErrorOccurred(_)
+                        ) && peer_mod_ids.contains(&mod_.workshop.id);
+
+                        if mod_.download_status == DownloadStatus::Downloaded {
+                            let id = mod_.workshop.id.clone();
+                            let bundle_checkbox = Checkbox::new(
+                                bundle_selection.contains(&id),
+                                "Add to bundle",
+                                move |selected| {
+                                    Message::BundleSelectionToggled(id.clone(), selected)
+                                },
+                            )
+                            .style(GeneralUiStyles);
+                            buttons_row = buttons_row.push(bundle_checkbox);
+
+                            let subscribe_label = if subscribed.contains(&mod_.workshop.id) {
+                                "Unsubscribe"
+                            } else {
+                                "Subscribe"
+                            };
+                            let subscribe_button =
+                                Button::new(&mut mod_.subscribe_button, Text::new(subscribe_label))
+                                    .style(GeneralUiStyles)
+                                    .on_press(Message::ToggleSubscribed(mod_.workshop.id.clone()));
+
+                            let pin_label = if pinned.contains(&mod_.workshop.id) {
+                                "Unpin version"
+                            } else {
+                                "Pin version"
+                            };
+                            let pin_button =
+                                Button::new(&mut mod_.pin_button, Text::new(pin_label))
+                                    .style(GeneralUiStyles)
+                                    .on_press(Message::TogglePinned(mod_.workshop.id.clone()));
+
+                            buttons_row = buttons_row.push(subscribe_button).push(pin_button);
+                        }
+
+                        let can_roll_back = matches!(
+                            mod_.download_status,
+                            DownloadStatus::Downloaded
+                                | DownloadStatus::NotDownloaded
+                                | DownloadStatus::ErrorOccurred(_)
+                        );
+                        if let (true, Some(version)) =
+                            (can_roll_back, rollback_targets.get(&mod_.workshop.id))
+                        {
+                            let action = if mod_.download_status == DownloadStatus::Downloaded {
+                                "Roll back"
+                            } else {
+                                "Restore"
+                            };
+                            let rollback_button = Button::new(
+                                &mut mod_.rollback_button,
+                                Text::new(format!(
+                                    "{} to version from {}",
+                                    action,
+                                    format_date(version)
+                                )),
+                            )
+                            .style(GeneralUiStyles)
+                            .on_press(Message::RollBackMod(mod_.workshop.id.clone(), *version));
+                            buttons_row = buttons_row.push(rollback_button);
+                        }
+
+                        if can_get_from_peer {
+                            let peer_download_button = Button::new(
+                                &mut mod_.peer_download_button,
+                                Text::new("Get from peer"),
+                            )
+                            .style(DownloadModButton)
+                            .on_press(Message::DownloadFromPeer(mod_.workshop.id.clone()));
+                            buttons_row = buttons_row.push(peer_download_button);
+                        }
+
+                        if !mod_.delisted {
+                            let refresh_button =
+                                Button::new(&mut mod_.refresh_button, Text::new("Refresh details"))
+                                    .style(GeneralUiStyles)
+                                    .on_press(Message::RefreshModDetails(mod_.workshop.id.clone()));
+                            buttons_row = buttons_row.push(refresh_button);
+                        }
+
+                        if removable_with_dependencies.contains(&mod_.workshop.id) {
+                            let remove_all_button = Button::new(
+                                &mut mod_.remove_with_dependencies_button,
+                                Text::new("Remove with unused dependencies"),
+                            )
+                            .style(RemoveModButton)
+                            .on_press(Message::RemoveModWithDependencies(mod_.workshop.id.clone()));
+                            buttons_row = buttons_row.push(remove_all_button);
+                        }
+
+                        let installed_and_delisted =
+                            mod_.delisted && mod_.download_status == DownloadStatus::Downloaded;
+
+                        if installed_and_delisted {
+                            if !kept_delisted.contains(&mod_.workshop.id) {
+                                let keep_button =
+                                    Button::new(&mut mod_.keep_button, Text::new("Keep"))
+                                        .style(GeneralUiStyles)
+                                        .on_press(Message::KeepDelistedMod(
+                                            mod_.workshop.id.clone(),
+                                        ));
+                                buttons_row = buttons_row.push(keep_button);
+                            }
+
+                            let backup_button =
+                                Button::new(&mut mod_.backup_button, Text::new("Back up"))
+                                    .style(GeneralUiStyles)
+                                    .on_press(Message::BackupMod(mod_.workshop.id.clone()));
+                            buttons_row = buttons_row.push(backup_button);
+                        }
 
                         // TODO: Don't unwrap this here (if it crashes will explode the program)
                         let bytes_size = mod_.workshop.file_size;
@@ -650,6 +3382,8 @@ impl Application for BaronyModManager {
                             .push(mod_image)
                             .push(views_label)
                             .push(votes_row)
+                            .push(favorites_label)
+                            .push(subscriptions_label)
                             .push(dates_col)
                             .push(size_col)
                             .push(buttons_row);
@@ -674,15 +3408,99 @@ impl Application for BaronyModManager {
 
                         let mod_description = Text::new(description).color(Color::WHITE);
 
-                        let mod_info_description = Column::new()
-                            .spacing(10)
-                            .push(mod_title)
-                            .push(mod_description);
+                        let author = if mod_.workshop.creator.is_empty() {
+                            "Unknown"
+                        } else {
+                            &mod_.workshop.creator
+                        };
+                        let mod_author = Text::new(format!("By {}", author)).color(Color::WHITE);
+
+                        let mut mod_info_description =
+                            Column::new().spacing(10).push(mod_title).push(mod_author);
+
+                        if !mod_.extra_previews.is_empty() {
+                            let previews_row = mod_
+                                .extra_previews
+                                .iter()
+                                .fold(Row::new().spacing(5), |row, preview| {
+                                    row.push(Image::new(preview.clone()))
+                                });
+                            mod_info_description = mod_info_description.push(previews_row);
+                        }
+
+                        let mod_info_description = mod_info_description.push(mod_description);
 
                         let status_message = format!("Status: {}", mod_.download_status);
-                        let mod_download_status =
+                        let mut mod_download_status =
                             Column::new().push(Text::new(status_message).color(Color::WHITE));
 
+                        if installed_and_delisted && kept_delisted.contains(&mod_.workshop.id) {
+                            mod_download_status = mod_download_status.push(
+                                Text::new(
+                                    "Kept: removed from the workshop, never removed by syncs \
+                                     or pruning.",
+                                )
+                                .color(Color::WHITE),
+                            );
+                        } else if installed_and_delisted {
+                            mod_download_status = mod_download_status.push(
+                                Text::new(
+                                    "Delisted: this mod was removed from the workshop, so it \
+                                     can't be updated or downloaded again.",
+                                )
+                                .color(Color::WHITE),
+                            );
+                        } else if mod_.delisted {
+                            mod_download_status = mod_download_status.push(
+                                Text::new("No longer available in the workshop catalog")
+                                    .color(Color::WHITE),
+                            );
+                        } else if mod_.outside_catalog {
+                            mod_download_status = mod_download_status.push(
+                                Text::new(
+                                    "Added by id, not in the catalog yet. Its details are \
+                                     filled in once the catalog has it.",
+                                )
+                                .color(Color::WHITE),
+                            );
+                        }
+
+                        if outdated.contains(&mod_.workshop.id)
+                            && mod_.download_status == DownloadStatus::Downloaded
+                        {
+                            let pinned_note = if pinned.contains(&mod_.workshop.id) {
+                                ", but this version is pinned"
+                            } else {
+                                ""
+                            };
+                            mod_download_status = mod_download_status.push(
+                                Text::new(format!(
+                                    "Update available: the workshop has the version of {}{}",
+                                    format_date(&mod_.workshop.time_updated),
+                                    pinned_note
+                                ))
+                                .color(Color::WHITE),
+                            );
+                        }
+
+                        if whats_new.new.contains(&mod_.workshop.id) {
+                            mod_download_status = mod_download_status
+                                .push(Text::new("New since the last launch").color(Color::WHITE));
+                        } else if whats_new.updated.contains(&mod_.workshop.id) {
+                            mod_download_status = mod_download_status.push(
+                                Text::new("Updated since the last launch").color(Color::WHITE),
+                            );
+                        }
+
+                        if !mod_.workshop.children.is_empty() {
+                            let dependencies_message = format!(
+                                "Requires {} other workshop item(s)",
+                                mod_.workshop.children.len()
+                            );
+                            mod_download_status = mod_download_status
+                                .push(Text::new(dependencies_message).color(Color::WHITE));
+                        }
+
                         let mod_info = Column::new()
                             .spacing(10)
                             .push(mod_info_description)
@@ -709,12 +3527,48 @@ impl Application for BaronyModManager {
         };
 
         // -------------- Everything together --------------
-        let all_content = Column::new()
-            .spacing(20)
-            .push(header)
-            .push(search_options)
-            .push(main_section)
-            .push(bottom_inputs);
+        let mut all_content = Column::new().spacing(20).push(header).push(search_options);
+
+        if let Some(status) = &self.status_message {
+            all_content = all_content.push(Text::new(status.clone()).color(Color::WHITE));
+        }
+
+        if let Some((id, message)) = removal_message {
+            let buttons = Row::new()
+                .spacing(10)
+                .push(
+                    Button::new(&mut self.confirm_removal_button, Text::new("Remove anyway"))
+                        .style(RemoveModButton)
+                        .on_press(Message::ConfirmRemoveMod(id.clone())),
+                )
+                .push(
+                    Button::new(
+                        &mut self.remove_with_dependencies_button,
+                        Text::new("Remove with unused dependencies"),
+                    )
+                    .style(RemoveModButton)
+                    .on_press(Message::RemoveModWithDependencies(id)),
+                )
+                .push(
+                    Button::new(&mut self.cancel_removal_button, Text::new("Cancel"))
+                        .style(GeneralUiStyles)
+                        .on_press(Message::CancelRemoval),
+                );
+
+            let content = Column::new()
+                .spacing(10)
+                .push(Text::new(message).color(Color::WHITE))
+                .push(buttons);
+
+            let removal_prompt = Container::new(content)
+                .padding(10)
+                .width(Length::Fill)
+                .style(ModCardUiStyles);
+
+            all_content = all_content.push(removal_prompt);
+        }
+
+        let all_content = all_content.push(main_section).push(bottom_inputs);
 
         Container::new(all_content)
             .width(Length::Fill)
@@ -727,15 +3581,24 @@ impl Application for BaronyModManager {
 
 fn sort_mods(state: &mut BaronyModManager) {
     if let Some(sorter) = &state.selected_sorter {
+        let trends = &state.trends;
         if let Some(mods) = &mut state.mods {
             match sorter {
                 Sorter::None => (),
                 other => mods.sort_unstable_by(|a, b| match other {
                     Sorter::Size => a.workshop.file_size.cmp(&b.workshop.file_size),
                     Sorter::Views => a.workshop.views.cmp(&b.workshop.views),
+                    Sorter::Favorites => a.workshop.favorited.cmp(&b.workshop.favorited),
+                    Sorter::Subscriptions => {
+                        a.workshop.subscriptions.cmp(&b.workshop.subscriptions)
+                    }
                     Sorter::Created => a.workshop.time_created.cmp(&b.workshop.time_created),
                     Sorter::Updated => a.workshop.time_updated.cmp(&b.workshop.time_updated),
                     Sorter::VoteScore => a.workshop.votes.up.cmp(&b.workshop.votes.up),
+                    Sorter::Trending => {
+                        let trend = |id: &String| trends.get(id).copied().unwrap_or_default();
+                        trend(&a.workshop.id).cmp(&trend(&b.workshop.id))
+                    }
                     Sorter::None => panic!("Should never match"),
                 }),
             };
@@ -746,3 +3609,708 @@ fn sort_mods(state: &mut BaronyModManager) {
         }
     }
 }
+
+/// Keeps the fetched catalog in the history, unless it's the same as the last one, and
+/// works out what changed since the last launch and the trends.
+fn record_catalog_snapshot(state: &mut BaronyModManager, mods: &[SteamWorkshopMod]) {
+    let snapshot = CatalogSnapshot::new(mods);
+    let unchanged = state
+        .history
+        .last()
+        .map_or(false, |last| last.mods == snapshot.mods);
+
+    if !unchanged {
+        if let Err(err) = filesystem::persist_catalog_snapshot(&snapshot) {
+            state.status_message = Some(format!("Could not save the catalog history: {}", err));
+        }
+
+        let keep_since = snapshot.time - chrono::Duration::days(HISTORY_DAYS);
+        let _ = filesystem::prune_catalog_history(keep_since);
+        state.history.retain(|old| old.time >= keep_since);
+        state.history.push(snapshot.clone());
+    }
+
+    if let Some(baseline) = &state.history_baseline {
+        state.whats_new = WhatsNew::between(baseline, &snapshot);
+    }
+    state.trends = history::trends(&state.history);
+}
+
+fn find_mod<'a>(state: &'a mut BaronyModManager, id: &str) -> Option<&'a mut BaronyMod> {
+    state
+        .mods
+        .as_mut()
+        .and_then(|mods| mods.iter_mut().find(|mod_| mod_.workshop.id == id))
+}
+
+/// Installs the mod after all the workshop items it requires, queueing the missing ones
+/// first. The install fails right away if the dependency graph has cycles or points to
+/// items that are not in the catalog.
+fn install_mod(state: &mut BaronyModManager, id: String) -> Command<Message> {
+    let mods = match &state.mods {
+        Some(mods) => mods,
+        None => return Command::none(),
+    };
+
+    if let Err(err) = resolve_install_order(&id, &dependency_graph(mods)) {
+        if let Some(selected_mod) = find_mod(state, &id) {
+            selected_mod.download_status = DownloadStatus::ErrorOccurred(err.to_string());
+        }
+        return Command::none();
+    }
+
+    let selected_mod = match mods.iter().find(|mod_| mod_.workshop.id == id) {
+        Some(selected_mod) => selected_mod,
+        None => return Command::none(),
+    };
+    let missing = selected_mod
+        .workshop
+        .children
+        .iter()
+        .filter(|child| {
+            mods.iter().any(|mod_| {
+                mod_.workshop.id == **child && mod_.download_status != DownloadStatus::Downloaded
+            })
+        })
+        .cloned()
+        .collect::<HashSet<_>>();
+
+    if missing.is_empty() {
+        return start_download(state, id);
+    }
+
+    // Dependencies that are already on their way only need to be waited for
+    let to_install = missing
+        .iter()
+        .filter(|child| {
+            mods.iter().any(|mod_| {
+                mod_.workshop.id == **child
+                    && matches!(
+                        mod_.download_status,
+                        DownloadStatus::NotDownloaded | DownloadStatus::ErrorOccurred(_)
+                    )
+            })
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if let Some(selected_mod) = find_mod(state, &id) {
+        selected_mod.download_status = DownloadStatus::WaitingForDependencies;
+    }
+    state.pending_installs.insert(id, missing);
+
+    let mut commands = vec![];
+    for child in to_install {
+        commands.push(install_mod(state, child));
+    }
+    Command::batch(commands)
+}
+
+fn downloads_in_progress(state: &BaronyModManager) -> bool {
+    state.mods.iter().flatten().any(|mod_| {
+        matches!(
+            mod_.download_status,
+            DownloadStatus::WaitingForDependencies
+                | DownloadStatus::Preparing
+                | DownloadStatus::Downloading
+                | DownloadStatus::Removing
+                | DownloadStatus::RollingBack
+        )
+    })
+}
+
+/// Makes the named installation the one in use, with its own directory, installed mods,
+/// collections, stored versions, watchlist and API settings.
+fn switch_installation(state: &mut BaronyModManager, name: String) -> Command<Message> {
+    if downloads_in_progress(state) {
+        state.status_message =
+            Some("Wait for the downloads to finish before switching installations".to_string());
+        return Command::none();
+    }
+
+    let installation = match state
+        .installations
+        .iter()
+        .find(|installation| installation.name == name)
+    {
+        Some(installation) => installation.clone(),
+        None => return Command::none(),
+    };
+
+    // The share serves the mods of the installation it was started from. Dropping the
+    // sender stops it
+    if state.share_stop.take().is_some() {
+        state.share_port = None;
+    }
+
+    state.active_installation = installation.name;
+    state.barony_dir_str = installation.barony_directory_path;
+    state.barony_dir_valid = barony_dir_valid(&state.barony_dir_str);
+    state.steam_api_key_str = installation.steam_api_key.unwrap_or_default();
+    state.steam_api_url_str = installation.steam_api_url.unwrap_or_default();
+    state.refresh_interval = installation
+        .refresh_interval_minutes
+        .map(RefreshInterval)
+        .unwrap_or_default();
+    state.installed = filesystem::load_installed_mods(&state.active_installation);
+    state.collections = filesystem::load_collections(&state.active_installation)
+        .into_iter()
+        .map(TrackedCollection::new)
+        .collect();
+    state.stored_versions = versions::list_stored_versions(&state.active_installation);
+    state.watchlist = filesystem::load_watchlist(&state.active_installation);
+    state.notifications = filesystem::load_notifications(&state.active_installation);
+    state.pending_installs.clear();
+    state.removal_prompt = None;
+    state.fingerprint = None;
+    state.status_message = None;
+    state.removable_with_dependencies = None;
+    save_settings(state);
+
+    // Cards of mods outside the catalog were only shown for the installs of the previous
+    // installation, and this one's are shown instead
+    let installed = &state.installed;
+    if let Some(mods) = &mut state.mods {
+        mods.retain(|mod_| {
+            !(mod_.delisted || mod_.outside_catalog) || installed.contains_key(&mod_.workshop.id)
+        });
+    }
+    let delisted_installs = if state.mods.is_some() && state.barony_dir_valid {
+        state
+            .installed
+            .iter()
+            .filter(|(id, _)| {
+                state
+                    .mods
+                    .iter()
+                    .flatten()
+                    .all(|mod_| mod_.workshop.id != **id)
+            })
+            .filter(|(_, installed)| {
+                filesystem::is_mod_folder_present(&state.barony_dir_str, &installed.folder)
+            })
+            .map(|(_, installed)| installed.workshop.clone())
+            .collect::<Vec<_>>()
+    } else {
+        vec![]
+    };
+
+    // The cards show what's installed in this installation
+    let barony_dir = &state.barony_dir_str;
+    let barony_dir_valid = state.barony_dir_valid;
+    let installed = &state.installed;
+    for mod_ in state.mods.iter_mut().flatten() {
+        let present = barony_dir_valid
+            && match installed.get(&mod_.workshop.id) {
+                Some(installed) => filesystem::is_mod_folder_present(barony_dir, &installed.folder),
+                None => filesystem::is_mod_downloaded(barony_dir, &mod_.workshop.title),
+            };
+        mod_.download_status = if present {
+            DownloadStatus::Downloaded
+        } else {
+            DownloadStatus::NotDownloaded
+        };
+    }
+
+    if state.barony_dir_valid {
+        let mut commands = delisted_installs
+            .into_iter()
+            .map(|mod_| {
+                Command::perform(
+                    build_barony_mod(
+                        state.http_client.clone(),
+                        state.barony_dir_str.clone(),
+                        mod_,
+                    ),
+                    |mut m| {
+                        m.delisted = true;
+                        Message::ModBuilt(Box::new(m))
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        commands.push(scan_local_mods(state, vec![]));
+        commands.push(refresh_fingerprint(state));
+        Command::batch(commands)
+    } else {
+        state.local_mods.clear();
+        Command::none()
+    }
+}
+
+fn open_archive_chooser(state: &mut BaronyModManager, dir: PathBuf) {
+    match ArchiveChooser::open(dir) {
+        Ok(chooser) => state.archive_chooser = Some(chooser),
+        Err(err) => state.status_message = Some(format!("Could not open the folder: {}", err)),
+    }
+}
+
+fn start_download(state: &mut BaronyModManager, id: String) -> Command<Message> {
+    let time_updated = find_mod(state, &id).map(|selected_mod| selected_mod.workshop.time_updated);
+    let cached = time_updated.map_or(false, |time_updated| {
+        archive_cache::is_archive_cached(&id, time_updated)
+    });
+
+    if let Some(selected_mod) = find_mod(state, &id) {
+        selected_mod.download_status = if cached {
+            DownloadStatus::Downloading
+        } else {
+            DownloadStatus::Preparing
+        };
+    }
+
+    let client = state.http_client.clone();
+    let target = ExtractTarget::new(state, &id);
+    Command::perform(
+        async move {
+            // Archives can be big, so they are read here rather than while updating. It
+            // may have been evicted since it was checked, then it's downloaded again
+            let zip_bytes = time_updated
+                .and_then(|time_updated| archive_cache::cached_archive(&id, time_updated));
+            if let Some(zip_bytes) = zip_bytes {
+                return target.extract(&id, zip_bytes);
+            }
+
+            let workshop_item_id = match id.parse::<u64>() {
+                Ok(workshop_item_id) => workshop_item_id,
+                Err(_) => return Message::ModDownloadError(id, "not a workshop id".to_string()),
+            };
+            match queue_download(client, workshop_item_id).await {
+                Ok(uuid) => Message::PreparingModDownload(id, uuid),
+                Err(err) => Message::ModDownloadError(id, err.to_string()),
+            }
+        },
+        |message| message,
+    )
+}
+
+/// Removes the mods in the background, keeping the removed versions to be able to roll
+/// back.
+fn remove_mods(state: &mut BaronyModManager, ids: Vec<String>) -> Command<Message> {
+    let mut commands = vec![];
+
+    for id in ids {
+        let installed = state.installed.get(&id).cloned();
+        let selected_mod = match find_mod(state, &id) {
+            Some(selected_mod) => selected_mod,
+            None => continue,
+        };
+        let folder = installed
+            .as_ref()
+            .map(|installed| installed.folder.clone())
+            .unwrap_or_else(|| selected_mod.workshop.title.clone());
+        selected_mod.download_status = DownloadStatus::Removing;
+
+        let installation = state.active_installation.clone();
+        let barony_dir = state.barony_dir_str.clone();
+        let removed_id = id.clone();
+        commands.push(Command::perform(
+            async move {
+                delete_mod_version(
+                    &installation,
+                    &barony_dir,
+                    &removed_id,
+                    installed.as_ref(),
+                    &folder,
+                )
+            },
+            move |result| Message::ModRemoved(id.clone(), result),
+        ));
+    }
+
+    Command::batch(commands)
+}
+
+fn delete_mod_version(
+    installation: &str,
+    barony_dir: &str,
+    id: &str,
+    installed: Option<&InstalledMod>,
+    folder: &str,
+) -> Result<(), String> {
+    if let Some(installed) = installed {
+        versions::store_version(installation, barony_dir, id, installed)
+            .map_err(|err| format!("could not keep the removed version: {}", err))?;
+    }
+    filesystem::delete_mod_from_disk(barony_dir, folder).map_err(|err| err.to_string())
+}
+
+/// Puts back a stored version of the mod in the background and pins it, so it's not
+/// updated again right away. The version it replaces is stored too.
+fn roll_back_mod(
+    state: &mut BaronyModManager,
+    id: String,
+    version: DateTime<Utc>,
+) -> Command<Message> {
+    let stored = match state
+        .stored_versions
+        .get(&id)
+        .and_then(|versions| versions.iter().find(|stored| stored.version() == version))
+        .cloned()
+    {
+        Some(stored) => stored,
+        None => {
+            state.status_message =
+                Some("Could not roll back: this version is no longer stored".to_string());
+            return Command::none();
+        }
+    };
+    let installation = state.active_installation.clone();
+    let barony_dir = state.barony_dir_str.clone();
+    let current = state.installed.get(&id).cloned();
+
+    if let Some(mod_) = find_mod(state, &id) {
+        mod_.download_status = DownloadStatus::RollingBack;
+    }
+    state.status_message = Some(format!(
+        "Rolling back {}...",
+        stored.installed.workshop.title
+    ));
+
+    let restored_id = id.clone();
+    Command::perform(
+        async move {
+            restore_mod_version(
+                &installation,
+                &barony_dir,
+                &restored_id,
+                current,
+                stored,
+                version,
+            )
+            .map(Box::new)
+        },
+        move |result| Message::ModRolledBack(id.clone(), version, result),
+    )
+}
+
+/// Swaps the current version of the mod with the stored one and returns the install
+/// record of the restored version.
+fn restore_mod_version(
+    installation: &str,
+    barony_dir: &str,
+    id: &str,
+    current: Option<InstalledMod>,
+    stored: StoredVersion,
+    version: DateTime<Utc>,
+) -> Result<InstalledMod, String> {
+    versions::roll_back(installation, barony_dir, id, current.as_ref(), &stored)
+        .map_err(|err| err.to_string())?;
+
+    let mut installed = current.unwrap_or_else(|| stored.installed.clone());
+    installed.folder = stored.installed.folder;
+    installed.installed_version = Some(version);
+    installed.pinned = true;
+    Ok(installed)
+}
+
+/// Hashes the installed mods in the background to show the fingerprint of the mod set.
+fn refresh_fingerprint(state: &BaronyModManager) -> Command<Message> {
+    if !state.barony_dir_valid {
+        return Command::none();
+    }
+
+    let barony_dir = state.barony_dir_str.clone();
+    let installed = state.installed.clone();
+    Command::perform(
+        async move {
+            create_lock_file(&barony_dir, &installed)
+                .ok()
+                .map(|lock| lock.fingerprint())
+        },
+        Message::FingerprintComputed,
+    )
+}
+
+/// Looks for folders in `mods/` that don't belong to any known mod. `extra_folders` are
+/// folders of mods that are known but not built yet.
+fn scan_local_mods(state: &BaronyModManager, extra_folders: Vec<String>) -> Command<Message> {
+    let known_folders = state
+        .mods
+        .iter()
+        .flatten()
+        .map(|mod_| filesystem::mod_folder_name(&mod_.workshop.title))
+        .chain(
+            state
+                .installed
+                .values()
+                .map(|installed| installed.folder.clone()),
+        )
+        .chain(extra_folders)
+        .collect::<Vec<_>>();
+
+    Command::perform(
+        build_local_mods(state.barony_dir_str.clone(), known_folders),
+        Message::LocalModsScanned,
+    )
+}
+
+fn local_mod_card(local: &mut LocalMod) -> Container<Message> {
+    let image = Image::new(local.image_handle.clone());
+    let title = Text::new(local.folder.clone()).size(25).color(Color::WHITE);
+    let description = Text::new(
+        "This folder is not managed by the mod manager. Link it to a workshop item to track it.",
+    )
+    .color(Color::WHITE);
+
+    let folder = local.folder.clone();
+    let link_input = TextInput::new(
+        &mut local.link_input,
+        "Workshop id or url",
+        &local.link_value,
+        move |value| Message::LocalModLinkInputChanged(folder.clone(), value),
+    )
+    .padding(5)
+    .width(Length::Units(300))
+    .style(GeneralUiStyles)
+    .size(20);
+
+    let link_button = Button::new(&mut local.link_button, Text::new("Link"))
+        .style(DownloadModButton)
+        .on_press(Message::LinkLocalMod(local.folder.clone()));
+
+    let link_row = Row::new()
+        .spacing(10)
+        .align_items(Align::Center)
+        .push(link_input)
+        .push(link_button);
+
+    let info = Column::new()
+        .spacing(10)
+        .push(title)
+        .push(description)
+        .push(link_row);
+
+    let card = Row::new().spacing(20).push(image).push(info);
+
+    Container::new(card)
+        .padding(10)
+        .width(Length::Fill)
+        .style(ModCardUiStyles)
+}
+
+fn describe_modpack_import(
+    state: &BaronyModManager,
+    modpack: &Modpack,
+    import: &ModpackImport,
+) -> String {
+    let title_of = |id: &String| {
+        state
+            .mods
+            .iter()
+            .flatten()
+            .find(|mod_| mod_.workshop.id == *id)
+            .map_or(id.clone(), |mod_| mod_.workshop.title.clone())
+    };
+
+    let mut report = format!(
+        "Modpack {}: installing {} mods, {} already installed.",
+        modpack.name.as_deref().unwrap_or("without name"),
+        import.to_install.len(),
+        import.already_installed.len()
+    );
+
+    if let Some(description) = &modpack.description {
+        report = format!("{} {}", report, description);
+    }
+
+    if !import.unavailable.is_empty() {
+        let unavailable = import.unavailable.iter().map(title_of).collect::<Vec<_>>();
+        report = format!(
+            "{}\nNot available in the workshop: {}.",
+            report,
+            unavailable.join(", ")
+        );
+    }
+
+    if !import.mismatched.is_empty() {
+        let mismatched = import
+            .mismatched
+            .iter()
+            .map(|mismatch| {
+                format!(
+                    "{} (expected {}, {} {})",
+                    mismatch.title,
+                    format_date(&mismatch.expected),
+                    if mismatch.installed {
+                        "installed"
+                    } else {
+                        "available"
+                    },
+                    format_date(&mismatch.available)
+                )
+            })
+            .collect::<Vec<_>>();
+        report = format!(
+            "{}\nDifferent versions than the modpack: {}.",
+            report,
+            mismatched.join(", ")
+        );
+    }
+
+    report
+}
+
+fn format_date(date: &DateTime<Utc>) -> String {
+    format!("{}/{}/{}", date.day(), date.month(), date.year())
+}
+
+fn record_installed(state: &mut BaronyModManager, workshop: &SteamWorkshopMod) {
+    match state.installed.get_mut(&workshop.id) {
+        // Linked mods keep living in their own folder
+        Some(installed) => installed.set_installed(workshop.clone()),
+        None => {
+            let folder = filesystem::mod_folder_name(&workshop.title);
+            state.installed.insert(
+                workshop.id.clone(),
+                InstalledMod::new(folder, workshop.clone()),
+            );
+        }
+    }
+    save_installed_mods(state);
+}
+
+/// Downloads the newer version of the subscribed mods that are not pinned.
+fn auto_update(state: &mut BaronyModManager) -> Command<Message> {
+    let mut ids = state
+        .installed
+        .iter()
+        .filter(|(_, installed)| installed.wants_auto_update())
+        .map(|(id, _)| id.clone())
+        .collect::<Vec<_>>();
+    ids.sort();
+
+    // Only the mods that are on disk and not being downloaded already
+    ids.retain(|id| {
+        state.mods.iter().flatten().any(|mod_| {
+            &mod_.workshop.id == id
+                && !mod_.delisted
+                && mod_.download_status == DownloadStatus::Downloaded
+        })
+    });
+
+    if ids.is_empty() {
+        return Command::none();
+    }
+
+    // The new version may require items that are not installed yet
+    state.status_message = Some(format!("Updating {} subscribed mods...", ids.len()));
+    let commands = ids
+        .into_iter()
+        .map(|id| install_mod(state, id))
+        .collect::<Vec<_>>();
+    Command::batch(commands)
+}
+
+/// The catalog lags behind the workshop, so items it doesn't have yet are shown right
+/// away and downloaded once Steam tells what they are.
+fn fetch_unknown_items(state: &mut BaronyModManager, unknown: Vec<String>) -> Command<Message> {
+    let barony_dir = state.barony_dir_str.clone();
+    let mods = state.mods.get_or_insert_with(Vec::new);
+    for id in &unknown {
+        match mods.iter_mut().find(|mod_| &mod_.workshop.id == id) {
+            Some(mod_) => mod_.download_status = DownloadStatus::Preparing,
+            None => {
+                let mut mod_ =
+                    build_offline_barony_mod(&barony_dir, SteamWorkshopMod::placeholder(id), None);
+                mod_.outside_catalog = true;
+                mod_.download_status = DownloadStatus::Preparing;
+                mods.push(mod_);
+            }
+        }
+    }
+    state.error_message = None;
+    state.loading_mods = false;
+
+    Command::perform(
+        get_published_file_details(
+            state.http_client.clone(),
+            steam_web_api(state),
+            unknown.clone(),
+        ),
+        move |result| {
+            Message::PastedDetailsFetched(unknown.clone(), result.map_err(|err| err.to_string()))
+        },
+    )
+}
+
+/// The cache can hold many archives, so they are counted in the background.
+fn read_cache_stats() -> Command<Message> {
+    Command::perform(
+        async { archive_cache::cache_stats() },
+        Message::CacheStatsRead,
+    )
+}
+
+fn steam_web_api(state: &BaronyModManager) -> SteamWebApi {
+    SteamWebApi::new(
+        Some(state.steam_api_url_str.clone()),
+        Some(state.steam_api_key_str.clone()),
+    )
+}
+
+/// Fetches the current metadata of the mods straight from Steam.
+fn refresh_details(state: &mut BaronyModManager, ids: Vec<String>) -> Command<Message> {
+    state.status_message = Some(format!("Refreshing the details of {} mods...", ids.len()));
+    Command::perform(
+        get_published_file_details(state.http_client.clone(), steam_web_api(state), ids),
+        |result| Message::ModDetailsRefreshed(result.map_err(|err| err.to_string())),
+    )
+}
+
+fn save_watchlist(state: &BaronyModManager) {
+    if let Err(err) = filesystem::persist_watchlist(&state.active_installation, &state.watchlist) {
+        println!("Could not save the watchlist: {}", err);
+    }
+}
+
+fn save_settings(state: &BaronyModManager) {
+    filesystem::persist_settings(filesystem::SettingsPersistance {
+        barony_directory_path: None,
+        installations: state.installations.clone(),
+        active_installation: state.active_installation.clone(),
+        steam_api_key: None,
+        steam_api_url: None,
+        refresh_interval_minutes: None,
+    });
+}
+
+fn active_installation_mut(state: &mut BaronyModManager) -> Option<&mut Installation> {
+    let active = &state.active_installation;
+    state
+        .installations
+        .iter_mut()
+        .find(|installation| &installation.name == active)
+}
+
+fn save_notifications(state: &BaronyModManager) {
+    if let Err(err) =
+        filesystem::persist_notifications(&state.active_installation, &state.notifications)
+    {
+        println!("Could not save the notifications: {}", err);
+    }
+}
+
+fn save_collections(state: &BaronyModManager) {
+    let collections = state
+        .collections
+        .iter()
+        .map(|tracked| tracked.collection.clone())
+        .collect::<Vec<_>>();
+
+    if let Err(err) = filesystem::persist_collections(&state.active_installation, &collections) {
+        println!("Could not save the collections: {}", err);
+    }
+}
+
+fn save_installed_mods(state: &mut BaronyModManager) {
+    // Saved whenever the installs change
+    state.removable_with_dependencies = None;
+
+    if let Err(err) =
+        filesystem::persist_installed_mods(&state.active_installation, &state.installed)
+    {
+        // TODO: Maybe use logger or something
+        println!("Could not save the installed mods: {}", err);
+    }
+}
@@ -0,0 +1,219 @@
+use std::{collections::HashMap, fs, io, path::Path};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_with::formats::Flexible;
+use serde_with::TimestampSeconds;
+
+use crate::data::{BaronyMod, DownloadStatus, InstalledMod};
+
+/// A shareable list of workshop mods, pinned to the versions they were exported with.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Modpack {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub mods: Vec<ModpackEntry>,
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModpackEntry {
+    pub id: String,
+    /// Only informative, makes the file readable by humans
+    #[serde(default)]
+    pub title: String,
+    #[serde_as(as = "TimestampSeconds<String, Flexible>")]
+    pub time_updated: DateTime<Utc>,
+}
+
+/// A mod whose version on disk, or in the catalog when it's not installed yet, is not
+/// the one the modpack expects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionMismatch {
+    pub id: String,
+    pub title: String,
+    pub expected: DateTime<Utc>,
+    pub available: DateTime<Utc>,
+    /// `available` is the installed version rather than the catalog one
+    pub installed: bool,
+}
+
+/// What importing a modpack would do to the current installs.
+#[derive(Debug, Clone, Default)]
+pub struct ModpackImport {
+    pub to_install: Vec<String>,
+    pub already_installed: Vec<String>,
+    /// Ids of the mods that are not in the catalog and can't be installed
+    pub unavailable: Vec<String>,
+    pub mismatched: Vec<VersionMismatch>,
+}
+
+/// Builds a modpack from every installed mod, pinned to the versions on disk.
+pub fn export_modpack<'a>(
+    name: Option<String>,
+    description: Option<String>,
+    installed: impl Iterator<Item = (&'a String, &'a InstalledMod)>,
+) -> Modpack {
+    let mut mods = installed
+        .map(|(id, installed)| ModpackEntry {
+            id: id.clone(),
+            title: installed.workshop.title.clone(),
+            time_updated: installed.version(),
+        })
+        .collect::<Vec<_>>();
+    mods.sort_by(|a, b| a.title.cmp(&b.title));
+
+    Modpack {
+        name,
+        description,
+        mods,
+    }
+}
+
+pub fn plan_modpack_import(
+    modpack: &Modpack,
+    mods: &[BaronyMod],
+    installed: &HashMap<String, InstalledMod>,
+) -> ModpackImport {
+    let mut import = ModpackImport::default();
+
+    for entry in &modpack.mods {
+        let mod_ = match mods.iter().find(|mod_| mod_.workshop.id == entry.id) {
+            Some(mod_) if !mod_.delisted => mod_,
+            _ => {
+                import.unavailable.push(entry.id.clone());
+                continue;
+            }
+        };
+
+        let to_install = matches!(
+            mod_.download_status,
+            DownloadStatus::NotDownloaded | DownloadStatus::ErrorOccurred(_)
+        );
+        let installed_version = installed
+            .get(&entry.id)
+            .filter(|_| !to_install)
+            .map(InstalledMod::version);
+        let available = installed_version.unwrap_or(mod_.workshop.time_updated);
+
+        if available != entry.time_updated {
+            import.mismatched.push(VersionMismatch {
+                id: entry.id.clone(),
+                title: mod_.workshop.title.clone(),
+                expected: entry.time_updated,
+                available,
+                installed: installed_version.is_some(),
+            });
+        }
+
+        if to_install {
+            import.to_install.push(entry.id.clone())
+        } else {
+            import.already_installed.push(entry.id.clone())
+        }
+    }
+
+    import
+}
+
+pub fn read_modpack(path: &Path) -> io::Result<Modpack> {
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+pub fn write_modpack(path: &Path, modpack: &Modpack) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(modpack)?;
+    fs::write(path, json)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::data::SteamWorkshopMod;
+
+    fn workshop(id: &str, time_updated: i64) -> SteamWorkshopMod {
+        let mut workshop = SteamWorkshopMod::placeholder(id);
+        workshop.time_updated = Utc.timestamp(time_updated, 0);
+        workshop
+    }
+
+    fn entry(id: &str, time_updated: i64) -> ModpackEntry {
+        ModpackEntry {
+            id: id.to_string(),
+            title: String::new(),
+            time_updated: Utc.timestamp(time_updated, 0),
+        }
+    }
+
+    #[test]
+    fn exports_the_versions_on_disk() {
+        let mut installed = InstalledMod::new("B".to_string(), workshop("2", 200));
+        installed.workshop.time_updated = Utc.timestamp(300, 0);
+        let installs = vec![("2".to_string(), installed)];
+
+        let modpack = export_modpack(
+            Some("Friday".to_string()),
+            Some("Co-op night".to_string()),
+            installs.iter().map(|(id, installed)| (id, installed)),
+        );
+
+        assert_eq!(modpack.description.as_deref(), Some("Co-op night"));
+        assert_eq!(modpack.mods[0].time_updated, Utc.timestamp(200, 0));
+    }
+
+    #[test]
+    fn plans_installs_and_reports_missing_mods() {
+        let mut mods = [
+            BaronyMod::for_tests(workshop("1", 100), DownloadStatus::NotDownloaded),
+            BaronyMod::for_tests(workshop("2", 100), DownloadStatus::Downloaded),
+            BaronyMod::for_tests(workshop("3", 100), DownloadStatus::NotDownloaded),
+        ];
+        mods[2].delisted = true;
+
+        let modpack = Modpack {
+            mods: vec![
+                entry("1", 100),
+                entry("2", 100),
+                entry("3", 100),
+                entry("4", 100),
+            ],
+            ..Modpack::default()
+        };
+        let installed = HashMap::new();
+        let import = plan_modpack_import(&modpack, &mods, &installed);
+
+        assert_eq!(import.to_install, ["1"]);
+        assert_eq!(import.already_installed, ["2"]);
+        assert_eq!(import.unavailable, ["3", "4"]);
+        assert!(import.mismatched.is_empty());
+    }
+
+    #[test]
+    fn compares_installed_mods_with_the_version_on_disk() {
+        // The catalog has the expected version, but an older one is installed
+        let mods = [
+            BaronyMod::for_tests(workshop("1", 200), DownloadStatus::Downloaded),
+            BaronyMod::for_tests(workshop("2", 300), DownloadStatus::NotDownloaded),
+        ];
+        let mut installed = HashMap::new();
+        let mut old_install = InstalledMod::new("One".to_string(), workshop("1", 100));
+        old_install.workshop.time_updated = Utc.timestamp(200, 0);
+        installed.insert("1".to_string(), old_install);
+
+        let modpack = Modpack {
+            mods: vec![entry("1", 200), entry("2", 200)],
+            ..Modpack::default()
+        };
+        let import = plan_modpack_import(&modpack, &mods, &installed);
+
+        assert_eq!(import.mismatched.len(), 2);
+        assert_eq!(import.mismatched[0].available, Utc.timestamp(100, 0));
+        assert!(import.mismatched[0].installed);
+        assert_eq!(import.mismatched[1].available, Utc.timestamp(300, 0));
+        assert!(!import.mismatched[1].installed);
+    }
+}
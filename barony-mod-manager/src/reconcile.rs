@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+use crate::{
+    data::{InstalledMod, SteamWorkshopMod},
+    dependencies::{resolve_install_order, DependencyGraph},
+    modpack::Modpack,
+};
+
+/// What has to be done to the `mods/` directory so it matches a manifest.
+#[derive(Debug, Clone, Default)]
+pub struct Plan {
+    pub install: Vec<PlanItem>,
+    pub update: Vec<PlanItem>,
+    pub remove: Vec<PlanItem>,
+    pub keep: Vec<PlanItem>,
+    /// Mods in the manifest that are not in the catalog, so can't be installed
+    pub unavailable: Vec<PlanItem>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlanItem {
+    pub id: String,
+    pub title: String,
+}
+
+impl Plan {
+    /// Whether applying the plan would change anything.
+    pub fn has_changes(&self) -> bool {
+        !(self.install.is_empty() && self.update.is_empty() && self.remove.is_empty())
+    }
+
+    /// The mods to install and update, each one after the planned mods it requires.
+    /// Fails when the requirements form a cycle or some are not in the catalog.
+    pub fn install_order(&self, catalog: &[SteamWorkshopMod]) -> Result<Vec<&PlanItem>, String> {
+        let graph = catalog
+            .iter()
+            .map(|mod_| (mod_.id.clone(), mod_.children.clone()))
+            .collect::<DependencyGraph>();
+        let planned = self
+            .install
+            .iter()
+            .chain(self.update.iter())
+            .collect::<Vec<_>>();
+
+        let mut ordered: Vec<&PlanItem> = vec![];
+        for item in &planned {
+            let order = resolve_install_order(&item.id, &graph)
+                .map_err(|err| format!("Could not install {}: {}", item.title, err))?;
+            for id in order {
+                if ordered.iter().any(|ordered_item| ordered_item.id == id) {
+                    continue;
+                }
+                if let Some(planned_item) =
+                    planned.iter().find(|planned_item| planned_item.id == id)
+                {
+                    ordered.push(planned_item);
+                }
+            }
+        }
+
+        Ok(ordered)
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sections = [
+            ("+", &self.install),
+            ("~", &self.update),
+            ("-", &self.remove),
+            ("!", &self.unavailable),
+        ];
+
+        for (symbol, items) in sections.iter() {
+            for item in items.iter() {
+                writeln!(f, "{} {} ({})", symbol, item.title, item.id)?;
+            }
+        }
+
+        write!(
+            f,
+            "Plan: {} to install, {} to update, {} to remove, {} unchanged, {} unavailable.",
+            self.install.len(),
+            self.update.len(),
+            self.remove.len(),
+            self.keep.len(),
+            self.unavailable.len()
+        )
+    }
+}
+
+/// Compares the manifest against the installed mods. Workshop items required by the
+/// mods in the manifest are part of the desired state too. A mod is updated when the
+/// installed version is older than the one pinned in the manifest. Tracked mods that are
+/// not desired are only removed when `prune` is set, except delisted mods the user chose
+/// to keep, and folders the manager doesn't track are never touched.
+pub fn plan(
+    manifest: &Modpack,
+    catalog: &[SteamWorkshopMod],
+    installed: &HashMap<String, InstalledMod>,
+    is_folder_present: impl Fn(&str) -> bool,
+    prune: bool,
+) -> Plan {
+    let mut plan = Plan::default();
+    let pinned_versions = manifest
+        .mods
+        .iter()
+        .map(|entry| (entry.id.as_str(), entry))
+        .collect::<HashMap<_, _>>();
+
+    for id in desired_ids(manifest, catalog) {
+        let entry = pinned_versions.get(id.as_str());
+        let catalog_mod = catalog.iter().find(|mod_| mod_.id == id);
+        let installed_mod = installed
+            .get(&id)
+            .filter(|installed| is_folder_present(&installed.folder));
+
+        let title = catalog_mod
+            .map(|mod_| mod_.title.clone())
+            .or_else(|| installed_mod.map(|installed| installed.workshop.title.clone()))
+            .or_else(|| entry.map(|entry| entry.title.clone()))
+            .unwrap_or_else(|| id.clone());
+        let item = PlanItem { id, title };
+
+        match (installed_mod, catalog_mod, entry) {
+            (Some(installed), Some(_), Some(entry)) if installed.version() < entry.time_updated => {
+                plan.update.push(item)
+            }
+            (Some(_), _, _) => plan.keep.push(item),
+            (None, Some(_), _) => plan.install.push(item),
+            (None, None, _) => plan.unavailable.push(item),
+        }
+    }
+
+    if prune {
+        let desired = plan
+            .keep
+            .iter()
+            .chain(plan.update.iter())
+            .map(|item| item.id.clone())
+            .collect::<Vec<_>>();
+
+        let mut extra = installed
+            .iter()
+            .filter(|(id, _)| !desired.contains(id))
+            .filter(|(_, installed)| !installed.keep_delisted)
+            .filter(|(_, installed)| is_folder_present(&installed.folder))
+            .map(|(id, installed)| PlanItem {
+                id: id.clone(),
+                title: installed.workshop.title.clone(),
+            })
+            .collect::<Vec<_>>();
+        extra.sort_by(|a, b| a.title.cmp(&b.title));
+        plan.remove = extra;
+    }
+
+    plan
+}
+
+/// Ids in the manifest followed by the ids of everything they require.
+fn desired_ids(manifest: &Modpack, catalog: &[SteamWorkshopMod]) -> Vec<String> {
+    let mut ids = manifest
+        .mods
+        .iter()
+        .map(|entry| entry.id.clone())
+        .collect::<Vec<_>>();
+
+    let mut i = 0;
+    while i < ids.len() {
+        if let Some(mod_) = catalog.iter().find(|mod_| mod_.id == ids[i]) {
+            for child in &mod_.children {
+                if !ids.contains(child) {
+                    ids.push(child.clone());
+                }
+            }
+        }
+        i += 1;
+    }
+
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::modpack::ModpackEntry;
+
+    fn workshop(id: &str, time_updated: i64, children: &[&str]) -> SteamWorkshopMod {
+        let mut workshop = SteamWorkshopMod::placeholder(id);
+        workshop.title = format!("Mod {}", id);
+        workshop.time_updated = Utc.timestamp(time_updated, 0);
+        workshop.children = children.iter().map(|child| child.to_string()).collect();
+        workshop
+    }
+
+    fn manifest(entries: &[(&str, i64)]) -> Modpack {
+        Modpack {
+            mods: entries
+                .iter()
+                .map(|(id, time_updated)| ModpackEntry {
+                    id: id.to_string(),
+                    title: String::new(),
+                    time_updated: Utc.timestamp(*time_updated, 0),
+                })
+                .collect(),
+            ..Modpack::default()
+        }
+    }
+
+    fn installed(entries: &[(&str, i64)]) -> HashMap<String, InstalledMod> {
+        entries
+            .iter()
+            .map(|(id, time_updated)| {
+                let folder = format!("Mod {}", id);
+                (
+                    id.to_string(),
+                    InstalledMod::new(folder, workshop(id, *time_updated, &[])),
+                )
+            })
+            .collect()
+    }
+
+    fn ids(items: &[PlanItem]) -> Vec<&str> {
+        items.iter().map(|item| item.id.as_str()).collect()
+    }
+
+    #[test]
+    fn plans_installs_updates_and_unavailable_mods() {
+        let catalog = vec![
+            workshop("1", 200, &["3"]),
+            workshop("2", 200, &[]),
+            workshop("3", 100, &[]),
+        ];
+        let installed = installed(&[("2", 100)]);
+        let plan = plan(
+            &manifest(&[("1", 200), ("2", 200), ("4", 100)]),
+            &catalog,
+            &installed,
+            |_| true,
+            false,
+        );
+
+        // The requirement of "1" is desired too
+        assert_eq!(ids(&plan.install), ["1", "3"]);
+        assert_eq!(ids(&plan.update), ["2"]);
+        assert_eq!(ids(&plan.unavailable), ["4"]);
+        assert!(plan.remove.is_empty());
+        assert!(plan.has_changes());
+    }
+
+    #[test]
+    fn orders_installs_and_updates_after_their_requirements() {
+        let catalog = vec![
+            workshop("X", 200, &["Y"]),
+            workshop("Y", 200, &["Z"]),
+            workshop("Z", 200, &[]),
+        ];
+        let installed = installed(&[("Z", 100)]);
+        let plan = plan(
+            &manifest(&[("Y", 200), ("X", 200), ("Z", 200)]),
+            &catalog,
+            &installed,
+            |_| true,
+            false,
+        );
+
+        let order = plan.install_order(&catalog).unwrap();
+        assert_eq!(
+            order
+                .iter()
+                .map(|item| item.id.as_str())
+                .collect::<Vec<_>>(),
+            ["Z", "Y", "X"]
+        );
+    }
+
+    #[test]
+    fn refuses_to_order_dependency_cycles() {
+        let catalog = vec![workshop("1", 200, &["2"]), workshop("2", 200, &["1"])];
+        let plan = plan(
+            &manifest(&[("1", 200)]),
+            &catalog,
+            &HashMap::new(),
+            |_| true,
+            false,
+        );
+
+        assert!(plan.install_order(&catalog).is_err());
+    }
+
+    #[test]
+    fn keeps_mods_at_or_above_the_pinned_version() {
+        let catalog = vec![workshop("1", 300, &[]), workshop("2", 300, &[])];
+        let installed = installed(&[("1", 200), ("2", 300)]);
+        let plan = plan(
+            &manifest(&[("1", 200), ("2", 200)]),
+            &catalog,
+            &installed,
+            |_| true,
+            false,
+        );
+
+        assert_eq!(ids(&plan.keep), ["1", "2"]);
+        assert!(!plan.has_changes());
+    }
+
+    #[test]
+    fn reinstalls_mods_whose_folder_is_gone() {
+        let catalog = vec![workshop("1", 100, &[])];
+        let installed = installed(&[("1", 100)]);
+        let plan = plan(
+            &manifest(&[("1", 100)]),
+            &catalog,
+            &installed,
+            |_| false,
+            true,
+        );
+
+        assert_eq!(ids(&plan.install), ["1"]);
+        assert!(plan.remove.is_empty());
+    }
+
+    #[test]
+    fn prunes_only_when_asked_and_never_kept_delisted_mods() {
+        let catalog = vec![workshop("1", 100, &[])];
+        let mut installed = installed(&[("1", 100), ("2", 100), ("3", 100)]);
+        installed.get_mut("3").unwrap().keep_delisted = true;
+        let manifest = manifest(&[("1", 100)]);
+
+        let plan_without_prune = plan(&manifest, &catalog, &installed, |_| true, false);
+        assert!(plan_without_prune.remove.is_empty());
+
+        let plan_with_prune = plan(&manifest, &catalog, &installed, |_| true, true);
+        assert_eq!(ids(&plan_with_prune.remove), ["2"]);
+    }
+}
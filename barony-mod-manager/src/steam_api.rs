@@ -1,28 +1,304 @@
-use iced::{button, image::Handle};
-use reqwest::{Client, Error};
+use iced::{button, image::Handle, text_input};
+use reqwest::Client;
+use serde_json::{json, Value};
 
 use crate::{
-    data::{BaronyMod, DownloadStatus, SteamWorkshopMod},
-    filesystem::is_mod_downloaded,
+    catalog::{parse_catalog, Catalog},
+    collections::WorkshopCollection,
+    data::{BaronyMod, DownloadStatus, LocalMod, SteamWorkshopMod, CATALOG_SCHEMA_VERSION},
+    filesystem::{find_mod_preview, is_mod_downloaded, list_mod_folders},
     images::{resize, to_handle},
 };
 
 static APP_IMAGES_SIZE: u32 = 180; // Pixels
+static EXTRA_PREVIEWS_SIZE: u32 = 56; // Pixels
+static MAX_EXTRA_PREVIEWS: usize = 3;
 static DEFAULT_IMAGE: &[u8; 4921] = include_bytes!("../resources/img/no_image.png");
+static DEFAULT_STEAM_API_URL: &str = "https://api.steampowered.com";
+pub(crate) static BARONY_APP_ID: u64 = 371970;
 
-pub async fn get_barony_workshop_mods(
-    client: Client,
-) -> Result<Vec<SteamWorkshopMod>, reqwest::Error> {
+pub async fn get_barony_workshop_mods(client: Client) -> Result<Catalog, reqwest::Error> {
     let endpoint =
         "https://raw.githubusercontent.com/arcticlimer/barony-mod-manager/master/data/mods.json";
-    let response = client
-        .get(endpoint)
+    let response = client.get(endpoint).send().await?.json::<Value>().await?;
+
+    Ok(parse_catalog(response))
+}
+
+/// Where and how the Steam Web API is called. The base url can point to a local fake
+/// API for testing.
+#[derive(Debug, Clone)]
+pub struct SteamWebApi {
+    pub base_url: String,
+    /// Optional Steam Web API key, which gives votes, required items and previews too
+    pub key: Option<String>,
+}
+
+impl Default for SteamWebApi {
+    fn default() -> Self {
+        SteamWebApi {
+            base_url: DEFAULT_STEAM_API_URL.to_string(),
+            key: None,
+        }
+    }
+}
+
+impl SteamWebApi {
+    /// Empty values fall back to the defaults.
+    pub fn new(base_url: Option<String>, key: Option<String>) -> SteamWebApi {
+        let not_empty = |value: Option<String>| {
+            value
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+
+        SteamWebApi {
+            base_url: not_empty(base_url)
+                .map(|url| url.trim_end_matches('/').to_string())
+                .unwrap_or_else(|| DEFAULT_STEAM_API_URL.to_string()),
+            key: not_empty(key),
+        }
+    }
+
+    pub(crate) fn endpoint(&self, method: &str) -> String {
+        format!("{}/{}/", self.base_url, method)
+    }
+}
+
+/// Fetches the items of a workshop collection, which doesn't need a Steam API key.
+pub async fn get_workshop_collection(
+    client: Client,
+    api: SteamWebApi,
+    id: String,
+) -> Result<WorkshopCollection, reqwest::Error> {
+    let details = client
+        .post(api.endpoint("ISteamRemoteStorage/GetCollectionDetails/v1"))
+        .form(&[("collectioncount", "1"), ("publishedfileids[0]", &id)])
         .send()
         .await?
-        .json::<Vec<SteamWorkshopMod>>()
+        .error_for_status()?
+        .json::<Value>()
         .await?;
 
-    Ok(response)
+    let mut children = details["response"]["collectiondetails"][0]["children"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    children.sort_by_key(|child| child["sortorder"].as_u64().unwrap_or(u64::MAX));
+
+    let items = children
+        .iter()
+        .filter_map(|child| child["publishedfileid"].as_str())
+        .map(|item| item.to_string())
+        .collect();
+
+    let file_details = client
+        .post(api.endpoint("ISteamRemoteStorage/GetPublishedFileDetails/v1"))
+        .form(&[("itemcount", "1"), ("publishedfileids[0]", &id)])
+        .send()
+        .await?
+        .json::<Value>()
+        .await
+        .unwrap_or_default();
+
+    let title = file_details["response"]["publishedfiledetails"][0]["title"]
+        .as_str()
+        .map(|title| title.to_string())
+        .unwrap_or_else(|| format!("Collection {}", id));
+
+    Ok(WorkshopCollection {
+        id,
+        title,
+        items,
+        installed: vec![],
+    })
+}
+
+/// Fetches the current metadata of workshop items straight from Steam, for when the
+/// catalog is outdated or doesn't have them yet. Uses `IPublishedFileService` when there
+/// is a key, `ISteamRemoteStorage` otherwise. Items that don't exist or are not Barony
+/// mods are left out.
+pub async fn get_published_file_details(
+    client: Client,
+    api: SteamWebApi,
+    ids: Vec<String>,
+) -> Result<Catalog, reqwest::Error> {
+    let request = match &api.key {
+        Some(key) => {
+            let mut params = vec![
+                ("key".to_string(), key.clone()),
+                ("includetags".to_string(), "true".to_string()),
+                ("includevotes".to_string(), "true".to_string()),
+                ("includechildren".to_string(), "true".to_string()),
+                ("includeadditionalpreviews".to_string(), "true".to_string()),
+                ("strip_description_bbcode".to_string(), "true".to_string()),
+            ];
+            for (i, id) in ids.iter().enumerate() {
+                params.push((format!("publishedfileids[{}]", i), id.clone()));
+            }
+            client
+                .get(api.endpoint("IPublishedFileService/GetDetails/v1"))
+                .query(&params)
+        }
+        None => {
+            let mut params = vec![("itemcount".to_string(), ids.len().to_string())];
+            for (i, id) in ids.iter().enumerate() {
+                params.push((format!("publishedfileids[{}]", i), id.clone()));
+            }
+            client
+                .post(api.endpoint("ISteamRemoteStorage/GetPublishedFileDetails/v1"))
+                .form(&params)
+        }
+    };
+
+    let response = request
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Value>()
+        .await?;
+
+    let entries = response["response"]["publishedfiledetails"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default()
+        .iter()
+        .filter(|details| details["result"].as_u64() == Some(1))
+        .filter(|details| {
+            // Each interface names it differently
+            let app_id = details["consumer_app_id"]
+                .as_u64()
+                .or_else(|| details["consumer_appid"].as_u64());
+            app_id == Some(BARONY_APP_ID)
+        })
+        .map(catalog_entry)
+        .collect::<Vec<_>>();
+
+    Ok(parse_catalog(Value::Array(entries)))
+}
+
+/// Turns the details of a workshop item into a catalog entry, as written to
+/// `data/mods.json`. Missing fields are repaired by `parse_catalog`.
+pub(crate) fn catalog_entry(details: &Value) -> Value {
+    let strings = |field: &str, key: &str| {
+        details[field]
+            .as_array()
+            .map(|items| {
+                items
+                    .iter()
+                    .filter(|item| field != "previews" || item["preview_type"].as_u64() == Some(0))
+                    .map(|item| item[key].clone())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    };
+
+    let mut entry = json!({
+        "id": details["publishedfileid"],
+        "title": details["title"],
+        "file_size": details["file_size"]
+            .as_str()
+            .and_then(|size| size.parse::<u64>().ok())
+            .or_else(|| details["file_size"].as_u64()),
+        "preview_url": details["preview_url"],
+        "description": if details["file_description"].is_string() {
+            &details["file_description"]
+        } else {
+            &details["description"]
+        },
+        "time_created": details["time_created"],
+        "time_updated": details["time_updated"],
+        "views": details["views"],
+        "favorited": details["favorited"],
+        "subscriptions": details["subscriptions"],
+        "tags": strings("tags", "tag"),
+        "votes": { "up": 0, "down": 0 },
+    });
+
+    // Only `IPublishedFileService` has these
+    if details["vote_data"].is_object() {
+        entry["votes"] = json!({
+            "up": details["vote_data"]["votes_up"],
+            "down": details["vote_data"]["votes_down"],
+        });
+        entry["schema"] = json!(CATALOG_SCHEMA_VERSION);
+        // Steam only gives the creator's id, the catalog builder resolves the name
+        entry["creator"] = json!("");
+        entry["children"] = json!(strings("children", "publishedfileid"));
+        entry["previews"] = json!(strings("previews", "url"));
+    }
+
+    entry
+}
+
+/// Builds the mods found in `mods/` whose folders are not in `known_folders`.
+pub async fn build_local_mods(barony_dir: String, known_folders: Vec<String>) -> Vec<LocalMod> {
+    list_mod_folders(&barony_dir)
+        .into_iter()
+        .filter(|folder| !known_folders.contains(folder))
+        .map(|folder| {
+            let image_handle = find_mod_preview(&barony_dir, &folder)
+                .and_then(|path| image::open(path).ok())
+                .map(|image| to_handle(&resize(&image, APP_IMAGES_SIZE, APP_IMAGES_SIZE)))
+                .unwrap_or_else(default_image);
+
+            LocalMod {
+                folder,
+                image_handle,
+                link_input: text_input::State::new(),
+                link_value: String::new(),
+                link_button: button::State::new(),
+            }
+        })
+        .collect()
+}
+
+/// Extracts every workshop item id from text with several urls or ids, separated by
+/// spaces, commas or new lines. Returns the ids and the parts that are not ids.
+pub fn parse_workshop_ids(input: &str) -> (Vec<String>, Vec<String>) {
+    let mut ids = vec![];
+    let mut invalid = vec![];
+
+    for part in input
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+        .filter(|part| !part.is_empty())
+    {
+        match parse_workshop_id(part) {
+            Some(id) if !ids.contains(&id) => ids.push(id),
+            Some(_) => {}
+            None => invalid.push(part.to_string()),
+        }
+    }
+
+    (ids, invalid)
+}
+
+/// Extracts the workshop item id from either a bare id or a workshop page url, such as
+/// `https://steamcommunity.com/sharedfiles/filedetails/?id=1517790477`. Numbers too big
+/// to be an id are refused.
+pub fn parse_workshop_id(input: &str) -> Option<String> {
+    let input = input.trim();
+    let id = match input
+        .split(&['?', '&'][..])
+        .find_map(|param| param.strip_prefix("id="))
+    {
+        Some(param) => param
+            .split(|c: char| !c.is_ascii_digit())
+            .next()
+            .unwrap_or_default(),
+        None => input,
+    };
+
+    if id.chars().all(|c| c.is_ascii_digit()) && id.parse::<u64>().is_ok() {
+        Some(id.to_string())
+    } else {
+        None
+    }
+}
+
+fn default_image() -> Handle {
+    let image = image::load_from_memory(DEFAULT_IMAGE).unwrap();
+    to_handle(&resize(&image, APP_IMAGES_SIZE, APP_IMAGES_SIZE))
 }
 
 pub async fn build_barony_mod(
@@ -30,24 +306,67 @@ pub async fn build_barony_mod(
     barony_dir: String,
     workshop_data: SteamWorkshopMod,
 ) -> BaronyMod {
-    let image = image::load_from_memory(DEFAULT_IMAGE).unwrap();
-    let resized = resize(&image, APP_IMAGES_SIZE, APP_IMAGES_SIZE);
-    let default_handle = to_handle(&resized);
+    let default_handle = default_image();
 
     let image_handle = if workshop_data.preview_url.is_empty() {
         default_handle
     } else {
-        match download_image(client, workshop_data.preview_url.clone()).await {
-            Ok(handle) => handle,
-            Err(_err) => default_handle,
-        }
+        download_image(
+            client.clone(),
+            workshop_data.preview_url.clone(),
+            APP_IMAGES_SIZE,
+        )
+        .await
+        .unwrap_or(default_handle)
     };
 
+    let mut extra_previews = vec![];
+    for url in workshop_data.previews.iter().take(MAX_EXTRA_PREVIEWS) {
+        if let Some(handle) = download_image(client.clone(), url.clone(), EXTRA_PREVIEWS_SIZE).await
+        {
+            extra_previews.push(handle);
+        }
+    }
+
+    new_barony_mod(&barony_dir, workshop_data, image_handle, extra_previews)
+}
+
+/// Builds a mod without going online, with the preview image bytes when there are some.
+pub fn build_offline_barony_mod(
+    barony_dir: &str,
+    workshop_data: SteamWorkshopMod,
+    preview: Option<&[u8]>,
+) -> BaronyMod {
+    let image_handle = preview
+        .and_then(|bytes| image_from_bytes(bytes, APP_IMAGES_SIZE))
+        .unwrap_or_else(default_image);
+
+    new_barony_mod(barony_dir, workshop_data, image_handle, vec![])
+}
+
+fn new_barony_mod(
+    barony_dir: &str,
+    workshop_data: SteamWorkshopMod,
+    image_handle: Handle,
+    extra_previews: Vec<Handle>,
+) -> BaronyMod {
     BaronyMod {
+        delisted: false,
+        outside_catalog: false,
         // is_downloaded: is_mod_downloaded(&barony_dir, &workshop_data.title.clone()),
         image_handle,
+        extra_previews,
         download_button: button::State::new(),
-        download_status: if is_mod_downloaded(&barony_dir, &workshop_data.title.clone()) {
+        remove_with_dependencies_button: button::State::new(),
+        keep_button: button::State::new(),
+        backup_button: button::State::new(),
+        peer_download_button: button::State::new(),
+        refresh_button: button::State::new(),
+        watch_button: button::State::new(),
+        subscribe_button: button::State::new(),
+        pin_button: button::State::new(),
+        rollback_button: button::State::new(),
+        download_status: if is_mod_downloaded(barony_dir, &workshop_data.title) {
             DownloadStatus::Downloaded
         } else {
             DownloadStatus::NotDownloaded
@@ -56,9 +375,49 @@ pub async fn build_barony_mod(
     }
 }
 
-pub async fn download_image(client: Client, url: String) -> Result<Handle, Error> {
-    let image_bytes = client.get(url).send().await?.bytes().await?;
-    let image = image::load_from_memory(&image_bytes).unwrap();
-    let resized = resize(&image, APP_IMAGES_SIZE, APP_IMAGES_SIZE);
-    Ok(to_handle(&resized))
+/// Returns `None` when the image can't be fetched or decoded, so a broken preview
+/// doesn't bring the mod down with it.
+pub async fn download_image(client: Client, url: String, size: u32) -> Option<Handle> {
+    let response = client.get(url).send().await.ok()?;
+    let image_bytes = response.bytes().await.ok()?;
+    image_from_bytes(&image_bytes, size)
+}
+
+fn image_from_bytes(image_bytes: &[u8], size: u32) -> Option<Handle> {
+    let image = image::load_from_memory(image_bytes).ok()?;
+    let resized = resize(&image, size, size);
+    Some(to_handle(&resized))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ids_and_workshop_urls() {
+        assert_eq!(
+            parse_workshop_id(" 1517790477 "),
+            Some("1517790477".to_string())
+        );
+        assert_eq!(
+            parse_workshop_id(
+                "https://steamcommunity.com/sharedfiles/filedetails/?id=1517790477&searchtext="
+            ),
+            Some("1517790477".to_string())
+        );
+        assert_eq!(parse_workshop_id("not an id"), None);
+    }
+
+    #[test]
+    fn refuses_ids_too_big_to_download() {
+        assert_eq!(
+            parse_workshop_id("18446744073709551615"),
+            Some("18446744073709551615".to_string())
+        );
+        assert_eq!(parse_workshop_id("18446744073709551616"), None);
+        assert_eq!(
+            parse_workshop_id("15177904771517790477151779047715177"),
+            None
+        );
+    }
 }
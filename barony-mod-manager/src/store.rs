@@ -0,0 +1,425 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::filesystem::{app_data_dir, copy_dir, extract_archive, hash_dir};
+
+fn store_dir() -> io::Result<PathBuf> {
+    app_data_dir()
+        .map(|dir| dir.join("store"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))
+}
+
+/// Where the holders of stored files are recorded, one file per holder. Holders are the
+/// mod folders linked to the files and the versions kept of them.
+fn links_dir(dir: &Path, hash: &str) -> PathBuf {
+    dir.join("links").join(hash)
+}
+
+/// The hash of the stored files each holder needs, to release them without looking
+/// through every record.
+fn holder_record(dir: &Path, holder: &Path) -> PathBuf {
+    dir.join("holders").join(holder_key(holder))
+}
+
+/// Extracts the archive once into the store, under the hash of its files, and links the
+/// mod folder to it, replacing what the folder had.
+pub fn install_archive(zip_bytes: &[u8], mod_folder: &Path) -> io::Result<()> {
+    install_files(mod_folder, |staging| extract_archive(zip_bytes, staging))
+}
+
+/// Puts the files written by `fill` in the store once, under their hash, and links the
+/// mod folder to them, replacing what the folder had. `fill` gets the folder to write in.
+pub fn install_files(
+    mod_folder: &Path,
+    fill: impl FnOnce(&Path) -> io::Result<()>,
+) -> io::Result<()> {
+    let dir = store_dir()?;
+    let hash = add_to_store(&dir, fill)?;
+    link_stored(&dir, &hash, mod_folder)
+}
+
+/// Puts the files of the folder in the store, unless it has them already, and records
+/// that `holder` needs them. Returns their hash. The folder is left as is.
+pub fn keep_folder(folder: &Path, holder: &Path) -> io::Result<String> {
+    let dir = store_dir()?;
+    let hash = hash_dir(folder)?;
+    if !dir.join(&hash).is_dir() {
+        add_to_store(&dir, |staging| copy_dir(folder, staging))?;
+    }
+
+    hold(&dir, &hash, holder)?;
+    Ok(hash)
+}
+
+/// Records that `holder` needs the stored files, so they are kept until it's released.
+/// The holder doesn't have to exist.
+pub fn hold_stored(hash: &str, holder: &Path) -> io::Result<()> {
+    hold(&store_dir()?, hash, holder)
+}
+
+/// Links the mod folder to the stored files, replacing what the folder had.
+pub fn link_mod_folder(hash: &str, mod_folder: &Path) -> io::Result<()> {
+    link_stored(&store_dir()?, hash, mod_folder)
+}
+
+/// Deletes the mod folder, or the link in its place, and the stored files nothing else
+/// needs.
+pub fn remove_mod_folder(mod_folder: &Path) -> io::Result<()> {
+    remove_folder(&store_dir()?, mod_folder)
+}
+
+/// Drops the record of the holder, and the stored files it was the last one to need.
+pub fn release(holder: &Path) -> io::Result<()> {
+    release_holder(&store_dir()?, holder)
+}
+
+/// Fills a staging folder and moves it into the store under the hash of its files.
+fn add_to_store(dir: &Path, fill: impl FnOnce(&Path) -> io::Result<()>) -> io::Result<String> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos());
+    let staging = dir.join(format!(".staging-{}-{}", std::process::id(), nanos));
+
+    let hash = match fill(&staging).and_then(|_| hash_dir(&staging)) {
+        Ok(hash) => hash,
+        Err(err) => {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(err);
+        }
+    };
+
+    let stored = dir.join(&hash);
+    if stored.is_dir() {
+        fs::remove_dir_all(&staging)?;
+    } else {
+        fs::rename(&staging, &stored)?;
+    }
+
+    Ok(hash)
+}
+
+fn link_stored(dir: &Path, hash: &str, mod_folder: &Path) -> io::Result<()> {
+    let stored = dir.join(hash);
+    if !stored.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "the files are not in the store",
+        ));
+    }
+    if let Some(parent) = mod_folder.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // Linked next to the folder first, so what the folder had is only replaced once the
+    // link works
+    let linking = sibling(mod_folder, "linking");
+    remove_folder_or_link(&linking)?;
+    if let Err(err) = link_dir(&stored, &linking) {
+        let _ = remove_folder_or_link(&linking);
+        return Err(err);
+    }
+    let replaced = sibling(mod_folder, "replaced");
+    remove_folder_or_link(&replaced)?;
+    let had_folder = fs::symlink_metadata(mod_folder).is_ok();
+    if had_folder {
+        if let Err(err) = fs::rename(mod_folder, &replaced) {
+            let _ = remove_folder_or_link(&linking);
+            return Err(err);
+        }
+    }
+    if let Err(err) = fs::rename(&linking, mod_folder) {
+        if had_folder {
+            let _ = fs::rename(&replaced, mod_folder);
+        }
+        let _ = remove_folder_or_link(&linking);
+        return Err(err);
+    }
+    remove_folder_or_link(&replaced)?;
+
+    // The files the folder had are only let go once it links to the new ones, since
+    // they may be the same
+    let previous = unhold(dir, mod_folder)?;
+    hold(dir, hash, mod_folder)?;
+
+    match previous {
+        Some(previous) if previous != hash => collect(dir, &previous),
+        _ => Ok(()),
+    }
+}
+
+/// A path next to the folder, like `Mod.linking` for `Mod`.
+fn sibling(folder: &Path, suffix: &str) -> PathBuf {
+    let mut name = folder.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    folder.with_file_name(name)
+}
+
+fn remove_folder(dir: &Path, mod_folder: &Path) -> io::Result<()> {
+    remove_folder_or_link(mod_folder)?;
+    release_holder(dir, mod_folder)
+}
+
+fn remove_folder_or_link(mod_folder: &Path) -> io::Result<()> {
+    let is_link = fs::symlink_metadata(mod_folder)
+        .map(|metadata| metadata.file_type().is_symlink())
+        .unwrap_or(false);
+    if is_link {
+        remove_link(mod_folder)
+    } else if mod_folder.exists() {
+        fs::remove_dir_all(mod_folder)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn remove_link(link: &Path) -> io::Result<()> {
+    fs::remove_file(link)
+}
+
+/// Removes directory symlinks and junctions alike.
+#[cfg(windows)]
+fn remove_link(link: &Path) -> io::Result<()> {
+    fs::remove_dir(link)
+}
+
+/// Symlinks the folder to the stored files, or makes a junction on Windows where
+/// symlinks aren't allowed, or hardlinks every file when neither works, or copies them
+/// where hardlinks aren't possible either.
+fn link_dir(stored: &Path, mod_folder: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(stored, mod_folder);
+    #[cfg(windows)]
+    let linked = std::os::windows::fs::symlink_dir(stored, mod_folder)
+        .or_else(|_| junction(stored, mod_folder));
+
+    if linked.is_ok() {
+        Ok(())
+    } else {
+        hard_link_dir(stored, mod_folder)
+    }
+}
+
+/// Junctions don't need the rights symlinks do, as long as both folders are on local
+/// drives.
+#[cfg(windows)]
+fn junction(target: &Path, link: &Path) -> io::Result<()> {
+    use std::os::windows::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    // `CREATE_NO_WINDOW`, so no console shows up
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    let status = Command::new("cmd")
+        .arg("/C")
+        .arg("mklink")
+        .arg("/J")
+        .arg(link)
+        .arg(target)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .creation_flags(CREATE_NO_WINDOW)
+        .status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "could not create a junction",
+        ))
+    }
+}
+
+fn hard_link_dir(source: &Path, target: &Path) -> io::Result<()> {
+    fs::create_dir_all(target)?;
+
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let target_path = target.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            hard_link_dir(&entry.path(), &target_path)?;
+        } else if fs::hard_link(entry.path(), &target_path).is_err() {
+            fs::copy(entry.path(), &target_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn holder_key(holder: &Path) -> String {
+    format!("{:x}", Sha256::digest(holder.to_string_lossy().as_bytes()))
+}
+
+fn hold(dir: &Path, hash: &str, holder: &Path) -> io::Result<()> {
+    let links = links_dir(dir, hash);
+    fs::create_dir_all(&links)?;
+    fs::write(
+        links.join(holder_key(holder)),
+        holder.to_string_lossy().as_bytes(),
+    )?;
+
+    let record = holder_record(dir, holder);
+    if let Some(parent) = record.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(record, hash)
+}
+
+fn release_holder(dir: &Path, holder: &Path) -> io::Result<()> {
+    match unhold(dir, holder)? {
+        Some(hash) => collect(dir, &hash),
+        None => Ok(()),
+    }
+}
+
+/// Drops the records of the holder, without deleting the files it needed, and returns
+/// their hash.
+fn unhold(dir: &Path, holder: &Path) -> io::Result<Option<String>> {
+    let hash = match held_hash(dir, holder) {
+        Some(hash) => hash,
+        None => return Ok(None),
+    };
+
+    remove_file_if_present(&links_dir(dir, &hash).join(holder_key(holder)))?;
+    remove_file_if_present(&holder_record(dir, holder))?;
+    Ok(Some(hash))
+}
+
+/// Folders linked before holders were recorded are found through their link.
+fn held_hash(dir: &Path, holder: &Path) -> Option<String> {
+    if let Ok(hash) = fs::read_to_string(holder_record(dir, holder)) {
+        return Some(hash.trim().to_string());
+    }
+
+    let target = fs::read_link(holder).ok()?;
+    let hash = target.file_name()?.to_str()?.to_string();
+    if links_dir(dir, &hash).join(holder_key(holder)).is_file() {
+        Some(hash)
+    } else {
+        None
+    }
+}
+
+/// Deletes the stored files when nothing holds them anymore.
+fn collect(dir: &Path, hash: &str) -> io::Result<()> {
+    let links = links_dir(dir, hash);
+    let held = match fs::read_dir(&links) {
+        Ok(mut entries) => entries.next().is_some(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => false,
+        Err(err) => return Err(err),
+    };
+    if held {
+        return Ok(());
+    }
+
+    let stored = dir.join(hash);
+    if stored.is_dir() {
+        fs::remove_dir_all(stored)?;
+    }
+    if links.is_dir() {
+        fs::remove_dir_all(links)?;
+    }
+    Ok(())
+}
+
+fn remove_file_if_present(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Puts a folder with one file in the store and returns its hash.
+    fn stored(dir: &Path, content: &str) -> String {
+        add_to_store(dir, |staging| {
+            fs::create_dir_all(staging)?;
+            fs::write(staging.join("data.txt"), content)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn keeps_files_until_the_last_holder_is_released() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("store");
+        let mods = root.path().join("mods");
+        let hash = stored(&dir, "one");
+
+        link_stored(&dir, &hash, &mods.join("A")).unwrap();
+        link_stored(&dir, &hash, &mods.join("B")).unwrap();
+        assert_eq!(fs::read_to_string(mods.join("A/data.txt")).unwrap(), "one");
+
+        remove_folder(&dir, &mods.join("A")).unwrap();
+        assert!(!mods.join("A").exists());
+        assert!(dir.join(&hash).is_dir());
+
+        remove_folder(&dir, &mods.join("B")).unwrap();
+        assert!(!dir.join(&hash).exists());
+        assert!(!links_dir(&dir, &hash).exists());
+    }
+
+    #[test]
+    fn holders_that_are_gone_still_keep_the_files() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("store");
+        let mod_folder = root.path().join("mods/A");
+        let hash = stored(&dir, "one");
+
+        hold(&dir, &hash, &root.path().join("versions/1/100")).unwrap();
+        link_stored(&dir, &hash, &mod_folder).unwrap();
+        remove_folder(&dir, &mod_folder).unwrap();
+        assert!(dir.join(&hash).is_dir());
+
+        release_holder(&dir, &root.path().join("versions/1/100")).unwrap();
+        assert!(!dir.join(&hash).exists());
+    }
+
+    #[test]
+    fn relinking_lets_go_of_the_previous_files() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("store");
+        let mod_folder = root.path().join("mods/A");
+        let old = stored(&dir, "old");
+        let new = stored(&dir, "new");
+
+        link_stored(&dir, &old, &mod_folder).unwrap();
+        // Linking the same files again doesn't drop them on the way
+        link_stored(&dir, &old, &mod_folder).unwrap();
+        assert!(dir.join(&old).is_dir());
+
+        link_stored(&dir, &new, &mod_folder).unwrap();
+        assert!(!dir.join(&old).exists());
+        assert_eq!(
+            fs::read_to_string(mod_folder.join("data.txt")).unwrap(),
+            "new"
+        );
+    }
+
+    #[test]
+    fn a_failed_link_keeps_what_the_folder_had() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("store");
+        let mod_folder = root.path().join("mods/A");
+        let hash = stored(&dir, "one");
+        link_stored(&dir, &hash, &mod_folder).unwrap();
+
+        assert!(link_stored(&dir, "missing", &mod_folder).is_err());
+        assert_eq!(
+            fs::read_to_string(mod_folder.join("data.txt")).unwrap(),
+            "one"
+        );
+        assert!(dir.join(&hash).is_dir());
+        assert_eq!(fs::read_dir(root.path().join("mods")).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    data::InstalledMod,
+    filesystem::{
+        app_data_dir, delete_mod_from_disk, installation_dir, is_mod_folder_present,
+        write_mod_to_disk,
+    },
+    store,
+};
+
+/// How many previous versions are kept for each mod.
+pub const VERSIONS_KEPT: usize = 3;
+/// Holds the hash of the version's files in the mod store.
+const HASH_FILE: &str = "hash";
+
+/// A previous version of a mod, kept in the versions store.
+#[derive(Debug, Clone)]
+pub struct StoredVersion {
+    /// The install record the mod had back then
+    pub installed: InstalledMod,
+    path: PathBuf,
+}
+
+impl StoredVersion {
+    pub fn version(&self) -> DateTime<Utc> {
+        self.installed.version()
+    }
+}
+
+/// Each installation keeps the versions of its own mods.
+fn versions_dir(installation: &str) -> io::Result<PathBuf> {
+    installation_dir(installation).map(|dir| dir.join("versions"))
+}
+
+/// Moves the versions stored before each installation had its own to `installation`,
+/// along with their hold on the files in the mod store.
+pub fn migrate_shared_versions(installation: &str) -> io::Result<()> {
+    let shared = match app_data_dir() {
+        Some(dir) => dir.join("versions"),
+        None => return Ok(()),
+    };
+    let dir = versions_dir(installation)?;
+    if !shared.is_dir() || dir.exists() {
+        return Ok(());
+    }
+
+    if let Some(parent) = dir.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(&shared, &dir)?;
+
+    for mod_entry in fs::read_dir(&dir)? {
+        let mod_entry = mod_entry?;
+        for version_entry in fs::read_dir(mod_entry.path())? {
+            let version_dir = version_entry?.path();
+            // Versions from before the mod store have their files with them
+            if let Ok(hash) = fs::read_to_string(version_dir.join(HASH_FILE)) {
+                let previous = shared
+                    .join(mod_entry.file_name())
+                    .join(version_dir.file_name().unwrap_or_default());
+                store::hold_stored(hash.trim(), &version_dir)?;
+                store::release(&previous)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Keeps the installed mod in the versions store before it's updated or removed, and
+/// forgets the oldest versions past the retention limit. The files go to the mod store,
+/// so a version that's installed somewhere doesn't take space twice.
+pub fn store_version(
+    installation: &str,
+    barony_path: &str,
+    id: &str,
+    installed: &InstalledMod,
+) -> io::Result<()> {
+    if !is_mod_folder_present(barony_path, &installed.folder) {
+        return Ok(());
+    }
+
+    let mod_dir = versions_dir(installation)?.join(id);
+    let version_dir = mod_dir.join(installed.version().timestamp().to_string());
+    forget_version(&version_dir)?;
+
+    fs::create_dir_all(&version_dir)?;
+    let hash = store::keep_folder(
+        &Path::new(barony_path).join("mods/").join(&installed.folder),
+        &version_dir,
+    )?;
+    fs::write(version_dir.join(HASH_FILE), &hash)?;
+    fs::write(
+        version_dir.join("version.json"),
+        serde_json::to_string(installed)?,
+    )?;
+
+    // The version just stored may be older than the others, like when rolling back, but
+    // it's the one that was on disk so it's never the one forgotten
+    let others = list_versions(&mod_dir)
+        .into_iter()
+        .filter(|stored| stored.path != version_dir);
+    for old in others.skip(VERSIONS_KEPT - 1) {
+        forget_version(&old.path)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the archive in the mod folder, keeping the version it replaces, `previous`, to
+/// be able to roll back.
+pub fn install_version(
+    installation: &str,
+    barony_path: &str,
+    id: &str,
+    previous: Option<&InstalledMod>,
+    folder: &str,
+    zip_bytes: Vec<u8>,
+) -> io::Result<()> {
+    if let Some(previous) = previous {
+        store_version(installation, barony_path, id, previous).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("could not keep the previous version: {}", err),
+            )
+        })?;
+    }
+
+    write_mod_to_disk(barony_path.to_string(), folder.to_string(), zip_bytes)
+}
+
+fn forget_version(version_dir: &Path) -> io::Result<()> {
+    if version_dir.exists() {
+        fs::remove_dir_all(version_dir)?;
+    }
+    store::release(version_dir)
+}
+
+/// Puts the stored version back in `mods/` in place of `current`, the installed version,
+/// which is stored in turn.
+pub fn roll_back(
+    installation: &str,
+    barony_path: &str,
+    id: &str,
+    current: Option<&InstalledMod>,
+    stored: &StoredVersion,
+) -> io::Result<()> {
+    let hash = stored_hash(stored)?;
+
+    // Storing the current version may forget the one being restored, so its files are
+    // held until they are linked
+    let restoring = stored.path.with_extension("restoring");
+    store::hold_stored(&hash, &restoring)?;
+    let result = swap_versions(installation, barony_path, id, current, stored, &hash);
+    store::release(&restoring)?;
+    result
+}
+
+fn swap_versions(
+    installation: &str,
+    barony_path: &str,
+    id: &str,
+    current: Option<&InstalledMod>,
+    stored: &StoredVersion,
+    hash: &str,
+) -> io::Result<()> {
+    if let Some(current) = current {
+        store_version(installation, barony_path, id, current)?;
+        if current.folder != stored.installed.folder {
+            delete_mod_from_disk(barony_path, &current.folder)?;
+        }
+    }
+
+    let mod_path = Path::new(barony_path)
+        .join("mods/")
+        .join(&stored.installed.folder);
+    store::link_mod_folder(hash, &mod_path)
+}
+
+/// Versions kept before the mod store have their own copy of the files, which is moved
+/// to the store the first time they're needed.
+fn stored_hash(stored: &StoredVersion) -> io::Result<String> {
+    if let Ok(hash) = fs::read_to_string(stored.path.join(HASH_FILE)) {
+        return Ok(hash.trim().to_string());
+    }
+
+    let files = stored.path.join("files");
+    let hash = store::keep_folder(&files, &stored.path)?;
+    fs::write(stored.path.join(HASH_FILE), &hash)?;
+    fs::remove_dir_all(files)?;
+    Ok(hash)
+}
+
+/// The stored versions of every mod of the installation by workshop id, newest first.
+pub fn list_stored_versions(installation: &str) -> HashMap<String, Vec<StoredVersion>> {
+    let entries = match versions_dir(installation).and_then(fs::read_dir) {
+        Ok(entries) => entries,
+        Err(_) => return HashMap::new(),
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let id = entry.file_name().into_string().ok()?;
+            let versions = list_versions(&entry.path());
+            if versions.is_empty() {
+                None
+            } else {
+                Some((id, versions))
+            }
+        })
+        .collect()
+}
+
+/// Versions stored in the folder of a mod, newest first.
+fn list_versions(mod_dir: &Path) -> Vec<StoredVersion> {
+    let mut versions = fs::read_dir(mod_dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter_map(|entry| {
+                    let content = fs::read_to_string(entry.path().join("version.json")).ok()?;
+                    let installed = serde_json::from_str(&content).ok()?;
+                    Some(StoredVersion {
+                        installed,
+                        path: entry.path(),
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    versions.sort_by_key(|stored| std::cmp::Reverse(stored.version()));
+    versions
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, TimestampSeconds};
+
+use crate::{data::SteamWorkshopMod, history::CatalogSnapshot};
+
+/// Mods and tags the user wants to hear about.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Watchlist {
+    #[serde(default)]
+    pub mods: BTreeSet<String>,
+    /// New mods with any of these tags are notified
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum NotificationKind {
+    Updated,
+    /// A new mod with the watched tag
+    NewWithTag(String),
+    Delisted,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    #[serde_as(as = "TimestampSeconds<i64>")]
+    pub time: DateTime<Utc>,
+    pub id: String,
+    pub title: String,
+    pub kind: NotificationKind,
+}
+
+impl Display for Notification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            NotificationKind::Updated => write!(f, "{} was updated", self.title),
+            NotificationKind::NewWithTag(tag) => write!(f, "New {} mod: {}", tag, self.title),
+            NotificationKind::Delisted => {
+                write!(f, "{} was removed from the workshop", self.title)
+            }
+        }
+    }
+}
+
+/// What the user should hear about in the catalog since the previous one: watched mods
+/// that were updated or delisted, and new mods with a watched tag.
+pub fn watch_notifications(
+    watchlist: &Watchlist,
+    previous: &CatalogSnapshot,
+    current: &[SteamWorkshopMod],
+) -> Vec<Notification> {
+    let now = Utc::now();
+    let notification = |id: &str, title: &str, kind| Notification {
+        time: now,
+        id: id.to_string(),
+        title: title.to_string(),
+        kind,
+    };
+
+    let mut notifications = vec![];
+    for mod_ in current {
+        match previous.mods.get(&mod_.id) {
+            Some(old) if old.time_updated < mod_.time_updated => {
+                if watchlist.mods.contains(&mod_.id) {
+                    notifications.push(notification(
+                        &mod_.id,
+                        &mod_.title,
+                        NotificationKind::Updated,
+                    ));
+                }
+            }
+            Some(_) => {}
+            None => {
+                if let Some(tag) = mod_.tags.iter().find(|tag| watchlist.tags.contains(*tag)) {
+                    notifications.push(notification(
+                        &mod_.id,
+                        &mod_.title,
+                        NotificationKind::NewWithTag(tag.clone()),
+                    ));
+                }
+            }
+        }
+    }
+
+    for id in &watchlist.mods {
+        if let Some(old) = previous.mods.get(id) {
+            if current.iter().all(|mod_| &mod_.id != id) {
+                notifications.push(notification(id, &old.title, NotificationKind::Delisted));
+            }
+        }
+    }
+
+    notifications.sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| a.title.cmp(&b.title)));
+    notifications
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn workshop(id: &str, time_updated: i64, tags: &[&str]) -> SteamWorkshopMod {
+        let mut workshop = SteamWorkshopMod::placeholder(id);
+        workshop.title = format!("Mod {}", id);
+        workshop.time_updated = Utc.timestamp(time_updated, 0);
+        workshop.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        workshop
+    }
+
+    fn watchlist(mods: &[&str], tags: &[&str]) -> Watchlist {
+        Watchlist {
+            mods: mods.iter().map(|id| id.to_string()).collect(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        }
+    }
+
+    fn kinds(notifications: &[Notification]) -> Vec<(&str, NotificationKind)> {
+        notifications
+            .iter()
+            .map(|notification| (notification.id.as_str(), notification.kind.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn notifies_watched_mods_and_new_mods_with_watched_tags() {
+        let previous = CatalogSnapshot::new(&[
+            workshop("1", 100, &[]),
+            workshop("2", 100, &[]),
+            workshop("3", 100, &[]),
+            workshop("5", 100, &["Maps"]),
+        ]);
+        let current = [
+            workshop("1", 200, &[]),
+            // Updated, but not watched
+            workshop("2", 200, &[]),
+            workshop("4", 100, &["Items", "Maps"]),
+            workshop("5", 200, &["Maps"]),
+            workshop("6", 100, &["Items"]),
+        ];
+        let notifications =
+            watch_notifications(&watchlist(&["1", "3", "7"], &["Maps"]), &previous, &current);
+
+        assert_eq!(
+            kinds(&notifications),
+            [
+                ("1", NotificationKind::Updated),
+                ("4", NotificationKind::NewWithTag("Maps".to_string())),
+                ("3", NotificationKind::Delisted),
+            ]
+        );
+        assert_eq!(
+            notifications[2].to_string(),
+            "Mod 3 was removed from the workshop"
+        );
+    }
+
+    #[test]
+    fn an_empty_watchlist_notifies_nothing() {
+        let previous = CatalogSnapshot::new(&[workshop("1", 100, &[])]);
+        let current = [workshop("1", 200, &[]), workshop("2", 100, &["Maps"])];
+
+        assert!(watch_notifications(&Watchlist::default(), &previous, &current).is_empty());
+    }
+}
@@ -1,6 +1,20 @@
-use std::fmt::{self, Display};
+use std::{
+    fmt::{self, Display},
+    net::SocketAddr,
+    path::PathBuf,
+};
 
-use crate::data::{BaronyMod, SteamWorkshopMod};
+use chrono::{DateTime, Utc};
+
+use crate::{
+    archive_cache::CacheStats,
+    bundle::BundledMod,
+    catalog::Catalog,
+    collections::WorkshopCollection,
+    data::{BaronyMod, InstalledMod, LocalMod},
+    lan_share::SharedMod,
+    lockfile::{Drift, ModDifference},
+};
 
 #[derive(Clone, Debug)]
 pub enum Message {
@@ -13,10 +27,11 @@ pub enum Message {
     SortingStrategySelected(SortingStrategy),
     CloseRequested,
     LoadMods,
+    ToggleCatalogReport,
     ErrorHappened(String),
 
     // Application inner workings' events
-    ModsFetched(Vec<SteamWorkshopMod>),
+    ModsFetched(Catalog),
     ModBuilt(Box<BaronyMod>),
     DownloadMod(String),
     PreparingModDownload(String, String),
@@ -24,6 +39,87 @@ pub enum Message {
     ModDownloaded(String),
     ModDownloadError(String, String), // mod id, error message
     RemoveMod(String),
+    ConfirmRemoveMod(String),
+    ModRemoved(String, Result<(), String>),
+    RemoveModWithDependencies(String),
+    CancelRemoval,
+    KeepDelistedMod(String),
+    BackupMod(String),
+    ModBackedUp(String, Result<PathBuf, String>),
+    LocalModsScanned(Vec<LocalMod>),
+    LocalModLinkInputChanged(String, String), // folder, input value
+    LinkLocalMod(String),
+    ArchivePathChanged(String),
+    InstallArchive(PathBuf),
+    OpenArchiveChooser,
+    ArchiveChooserDir(PathBuf),
+    ArchiveChosen(PathBuf),
+    CloseArchiveChooser,
+    ArchiveInstalled(Result<String, String>), // installed folder or error message
+    ModpackPathChanged(String),
+    ModpackNameChanged(String),
+    ModpackDescriptionChanged(String),
+    ExportModpack,
+    ImportModpack,
+    PruneModsToggled(bool),
+    PlanModpack,
+    ApplyPlan,
+    LockPathChanged(String),
+    WriteLockFile,
+    VerifyLockFile,
+    LockFileWritten(PathBuf, Result<usize, String>), // path, locked mods or error message
+    LockFileVerified(Result<(usize, Vec<Drift>), String>),
+    CompareModList,
+    ModListCompared(Result<Vec<ModDifference>, String>),
+    CloseComparison,
+    FingerprintComputed(Option<String>),
+    CopyFingerprint,
+    ToggleSharing,
+    SharingStopped(u16, Result<(), String>),
+    PeerAddressChanged(String),
+    FindPeers,
+    PeersFound(Result<Vec<SocketAddr>, String>),
+    ConnectPeer,
+    PeerModsFetched(String, Result<Vec<SharedMod>, String>),
+    DownloadFromPeer(String),
+    PeerModDownloaded(String, DateTime<Utc>),
+    BundleSelectionToggled(String, bool),
+    BundlePathChanged(String),
+    ExportBundle,
+    BundleExported(Result<usize, String>),
+    ImportBundle,
+    BundleImported(Result<Vec<BundledMod>, String>),
+    CollectionInputChanged(String),
+    AddCollection,
+    SyncCollection(String),
+    CollectionFetched(Result<WorkshopCollection, String>),
+    ForgetCollection(String),
+    PastedIdsChanged(String),
+    InstallPastedIds,
+    PastedDetailsFetched(Vec<String>, Result<Catalog, String>),
+    SteamApiKeyChanged(String),
+    SteamApiUrlChanged(String),
+    RefreshModDetails(String),
+    RefreshInstalledDetails,
+    ModDetailsRefreshed(Result<Catalog, String>),
+    ToggleWatchMod(String),
+    ToggleWatchTag,
+    ToggleNotifications,
+    ClearNotifications,
+    RefreshIntervalSelected(RefreshInterval),
+    ScheduledRefresh,
+    ScheduledRefreshFailed(String),
+    ToggleSubscribed(String),
+    TogglePinned(String),
+    RollBackMod(String, DateTime<Utc>),
+    ModRolledBack(String, DateTime<Utc>, Result<Box<InstalledMod>, String>),
+    PurgeArchiveCache,
+    ArchiveCachePurged(Result<(), String>),
+    CacheStatsRead(CacheStats),
+    InstallationSelected(String),
+    InstallationNameChanged(String),
+    AddInstallation,
+    RemoveInstallation,
 }
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
@@ -56,16 +152,22 @@ impl Default for PickableTag {
 pub enum Sorter {
     VoteScore,
     Views,
+    Favorites,
+    Subscriptions,
     Size,
     Updated,
     Created,
+    Trending,
     None,
 }
 
 impl Sorter {
-    pub const ALL: [Sorter; 6] = [
+    pub const ALL: [Sorter; 9] = [
+        Sorter::Trending,
         Sorter::VoteScore,
         Sorter::Views,
+        Sorter::Favorites,
+        Sorter::Subscriptions,
         Sorter::Size,
         Sorter::Updated,
         Sorter::Created,
@@ -81,9 +183,12 @@ impl Display for Sorter {
             match self {
                 Sorter::VoteScore => "Vote score",
                 Sorter::Views => "Views",
+                Sorter::Favorites => "Favorites",
+                Sorter::Subscriptions => "Subscribers",
                 Sorter::Size => "Size",
                 Sorter::Updated => "Date updated",
                 Sorter::Created => "Date created",
+                Sorter::Trending => "Trending",
                 Sorter::None => "Nothing",
             }
         )
@@ -130,14 +235,24 @@ pub enum Filter {
     Downloaded,
     NonDownloaded,
     Downloading,
+    WithDependencies,
+    NewSinceLastLaunch,
+    RecentlyUpdated,
+    Watched,
+    UpdateAvailable,
     None,
 }
 
 impl Filter {
-    pub const ALL: [Filter; 4] = [
+    pub const ALL: [Filter; 9] = [
+        Filter::UpdateAvailable,
+        Filter::Watched,
+        Filter::NewSinceLastLaunch,
+        Filter::RecentlyUpdated,
         Filter::Downloading,
         Filter::Downloaded,
         Filter::NonDownloaded,
+        Filter::WithDependencies,
         Filter::None,
     ];
 }
@@ -151,6 +266,11 @@ impl Display for Filter {
                 Filter::Downloaded => "Downloaded",
                 Filter::NonDownloaded => "Non Downloaded",
                 Filter::Downloading => "Downloading",
+                Filter::WithDependencies => "With dependencies",
+                Filter::NewSinceLastLaunch => "New since last launch",
+                Filter::RecentlyUpdated => "Recently updated",
+                Filter::Watched => "Watched",
+                Filter::UpdateAvailable => "Update available",
                 Filter::None => "None",
             }
         )
@@ -163,6 +283,37 @@ impl Default for Filter {
     }
 }
 
+/// Minutes between catalog refreshes while the app is open, 0 to never refresh
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RefreshInterval(pub u64);
+
+impl RefreshInterval {
+    pub const ALL: [RefreshInterval; 5] = [
+        RefreshInterval(0),
+        RefreshInterval(15),
+        RefreshInterval(30),
+        RefreshInterval(60),
+        RefreshInterval(180),
+    ];
+}
+
+impl Display for RefreshInterval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            0 => write!(f, "Never"),
+            60 => write!(f, "Every hour"),
+            minutes if minutes % 60 == 0 => write!(f, "Every {} hours", minutes / 60),
+            minutes => write!(f, "Every {} minutes", minutes),
+        }
+    }
+}
+
+impl Default for RefreshInterval {
+    fn default() -> RefreshInterval {
+        RefreshInterval(60)
+    }
+}
+
 /// Possible fields that can be used to sort the mods
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SortingStrategy {